@@ -0,0 +1,598 @@
+use actix_web::body::MessageBody;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderMap};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpRequest, HttpResponse, Responder, web};
+use anyhow::Result;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::time::{Duration, Instant, sleep};
+use tracing::{info, warn};
+
+use crate::forward_auth::{ForwardAuth, ForwardedIdentity};
+use crate::provision::{self, Secret};
+use crate::server::AppState;
+use crate::utils::paths;
+
+/// Cookie holding the session of a browser that logged in through the login page
+const SESSION_COOKIE: &str = "foundry_watcher_session";
+
+/// How long a browser session lasts
+const SESSION_LIFETIME: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Slows down guessing through the login page
+const FAILED_LOGIN_DELAY: Duration = Duration::from_secs(1);
+
+/// What an authenticated request may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Status, logs and events
+    Viewer,
+    /// Everything, including installing, restarting and backing up
+    Operator,
+}
+
+/// How a request proved who it is
+#[derive(Debug, Clone, PartialEq)]
+pub enum Grant {
+    /// Configured token, username and password, or a session created with them
+    Credentials,
+    /// The setup token, or a session created with it
+    Setup,
+    /// A user vouched for by a trusted forward-auth proxy
+    Forwarded { user: String, role: Role },
+}
+
+impl Grant {
+    /// Credentials and the setup token may do everything
+    pub fn role(&self) -> Role {
+        match self {
+            Grant::Forwarded { role, .. } => *role,
+            Grant::Credentials | Grant::Setup => Role::Operator,
+        }
+    }
+
+    fn user(&self) -> Option<&str> {
+        match self {
+            Grant::Forwarded { user, .. } => Some(user),
+            Grant::Credentials | Grant::Setup => None,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Grant::Credentials => "credentials",
+            Grant::Setup => "setup",
+            Grant::Forwarded { .. } => "forwarded",
+        }
+    }
+}
+
+/// Whether the installer UI sits behind the built-in proxy, whose connections all
+/// come from loopback
+#[derive(Debug, Clone, Copy)]
+pub struct BehindBuiltinProxy(pub bool);
+
+/// A request that was turned away, as written to the audit log
+struct Denial {
+    status: StatusCode,
+    user: Option<String>,
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct AuditEntry<'a> {
+    at: DateTime<Utc>,
+    client: Option<IpAddr>,
+    user: Option<&'a str>,
+    method: &'a str,
+    path: &'a str,
+    status: u16,
+    reason: &'a str,
+}
+
+struct Session {
+    grant: Grant,
+    expires: Instant,
+}
+
+/// Credentials for the installer and the management API
+pub struct Auth {
+    token: Option<Secret>,
+    basic: Option<(String, Secret)>,
+    /// Printed to the logs while the installer is active and nothing is configured.
+    /// Stops working once Foundry is installed.
+    setup_token: Mutex<Option<Secret>>,
+    sessions: Mutex<HashMap<String, Session>>,
+    forward_auth: Option<ForwardAuth>,
+    /// Denied requests are appended here as JSON lines
+    audit_log: PathBuf,
+}
+
+impl Auth {
+    /// Reads `AUTH_TOKEN` and `AUTH_USERNAME`/`AUTH_PASSWORD`, each secret optionally
+    /// from a file, and the `FORWARD_AUTH_*` settings
+    pub fn from_env() -> Result<Self> {
+        let token = provision::read_secret("AUTH_TOKEN")?;
+        let password = provision::read_secret("AUTH_PASSWORD")?;
+        let username = std::env::var("AUTH_USERNAME")
+            .ok()
+            .map(|username| username.trim().to_string())
+            .filter(|username| !username.is_empty());
+        let basic = match (username, password) {
+            (Some(username), Some(password)) => Some((username, password)),
+            (None, None) => None,
+            _ => {
+                warn!("⚠️ AUTH_USERNAME and AUTH_PASSWORD must be set together, ignoring them");
+                None
+            }
+        };
+
+        let audit_log = std::env::var("AUDIT_LOG")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| {
+                Path::new(&*paths::DATA_DIR)
+                    .join("Logs")
+                    .join("watcher-audit.log")
+            });
+
+        Ok(Self {
+            token,
+            basic,
+            setup_token: Mutex::new(None),
+            sessions: Mutex::new(HashMap::new()),
+            forward_auth: ForwardAuth::from_env(),
+            audit_log,
+        })
+    }
+
+    /// True when a token, username and password or a forward-auth proxy are configured
+    pub fn is_configured(&self) -> bool {
+        self.token.is_some() || self.basic.is_some() || self.forward_auth.is_some()
+    }
+
+    /// Generates and logs a setup token for the installer unless credentials are configured
+    pub fn issue_setup_token(&self) {
+        if self.is_configured() {
+            return;
+        }
+        let token = match provision::random_hex(16) {
+            Ok(token) => token,
+            Err(e) => {
+                warn!("⚠️ Failed to generate a setup token: {}", e);
+                return;
+            }
+        };
+        info!("🔑 Setup token for the installer: {}", token);
+        info!("🔑 It stops working once FoundryVTT is installed, set AUTH_TOKEN to keep access");
+        *self.setup_token.lock().unwrap() = Some(Secret::new(token));
+    }
+
+    /// Invalidates the setup token and every session created with it
+    pub fn revoke_setup_token(&self) {
+        if self.setup_token.lock().unwrap().take().is_some() {
+            info!("🔑 Setup token revoked");
+        }
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|_, session| session.grant != Grant::Setup);
+    }
+
+    fn check_token(&self, token: &str) -> Option<Grant> {
+        if let Some(configured) = &self.token
+            && secure_eq(configured.expose(), token)
+        {
+            return Some(Grant::Credentials);
+        }
+        match &*self.setup_token.lock().unwrap() {
+            Some(setup) if secure_eq(setup.expose(), token) => Some(Grant::Setup),
+            _ => None,
+        }
+    }
+
+    fn check_basic(&self, username: &str, password: &str) -> Option<Grant> {
+        let (expected_user, expected_password) = self.basic.as_ref()?;
+        // Both are compared so a wrong username takes as long as a wrong password
+        let user_ok = secure_eq(expected_user, username);
+        let password_ok = secure_eq(expected_password.expose(), password);
+        (user_ok && password_ok).then_some(Grant::Credentials)
+    }
+
+    fn check_session(&self, id: &str) -> Option<Grant> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+        sessions.retain(|_, session| session.expires > now);
+        sessions.get(id).map(|session| session.grant.clone())
+    }
+
+    /// Checks the `Authorization` header, then the session cookie
+    pub fn authorize(&self, headers: &HeaderMap, session: Option<&str>) -> Option<Grant> {
+        if let Some(grant) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| self.check_authorization(value))
+        {
+            return Some(grant);
+        }
+        session.and_then(|id| self.check_session(id))
+    }
+
+    fn check_authorization(&self, value: &str) -> Option<Grant> {
+        let (scheme, credentials) = value.trim().split_once(' ')?;
+        if scheme.eq_ignore_ascii_case("bearer") {
+            self.check_token(credentials.trim())
+        } else if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = BASE64.decode(credentials.trim()).ok()?;
+            let decoded = String::from_utf8(decoded).ok()?;
+            let (username, password) = decoded.split_once(':')?;
+            self.check_basic(username, password)
+        } else {
+            None
+        }
+    }
+
+    /// Works out who sent a request: credentials and sessions first, then the
+    /// forward-auth header. `Ok(None)` means the request carried nothing at all.
+    fn identify(
+        &self,
+        headers: &HeaderMap,
+        session: Option<&str>,
+        client: Option<IpAddr>,
+    ) -> Result<Option<Grant>, Denial> {
+        if let Some(grant) = self.authorize(headers, session) {
+            return Ok(Some(grant));
+        }
+        if headers.contains_key(header::AUTHORIZATION) {
+            return Err(Denial {
+                status: StatusCode::UNAUTHORIZED,
+                user: None,
+                reason: "invalid credentials".to_string(),
+            });
+        }
+
+        let Some(forward_auth) = &self.forward_auth else {
+            return Ok(None);
+        };
+        match forward_auth.identify(headers, client) {
+            ForwardedIdentity::Absent => Ok(None),
+            ForwardedIdentity::Untrusted { user } => Err(Denial {
+                status: StatusCode::UNAUTHORIZED,
+                user: Some(user),
+                reason: "user header from an address that is not a trusted proxy".to_string(),
+            }),
+            ForwardedIdentity::NoRole { user } => Err(Denial {
+                status: StatusCode::FORBIDDEN,
+                user: Some(user),
+                reason: "user has no role".to_string(),
+            }),
+            ForwardedIdentity::Granted { user, role } => Ok(Some(Grant::Forwarded { user, role })),
+        }
+    }
+
+    /// Logs a denied request and appends it to the audit log
+    fn audit(&self, denial: &Denial, client: Option<IpAddr>, method: &str, path: &str) {
+        warn!(
+            "🚫 Denied {} {} from {} ({}): {}",
+            method,
+            path,
+            client.map_or_else(|| "unknown".to_string(), |client| client.to_string()),
+            denial.user.as_deref().unwrap_or("anonymous"),
+            denial.reason
+        );
+
+        let entry = AuditEntry {
+            at: Utc::now(),
+            client,
+            user: denial.user.as_deref(),
+            method,
+            path,
+            status: denial.status.as_u16(),
+            reason: &denial.reason,
+        };
+        let written = serde_json::to_string(&entry)
+            .map_err(std::io::Error::other)
+            .and_then(|line| {
+                if let Some(parent) = self.audit_log.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.audit_log)?;
+                writeln!(file, "{}", line)
+            });
+        if let Err(e) = written {
+            warn!(
+                "⚠️ Failed to write to the audit log {}: {}",
+                self.audit_log.display(),
+                e
+            );
+        }
+    }
+
+    fn create_session(&self, grant: Grant) -> Result<String> {
+        let id = provision::random_hex(32)?;
+        self.sessions.lock().unwrap().insert(
+            id.clone(),
+            Session {
+                grant,
+                expires: Instant::now() + SESSION_LIFETIME,
+            },
+        );
+        Ok(id)
+    }
+}
+
+/// Compares without returning early, so timing does not reveal how much of a secret matched
+fn secure_eq(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[derive(Serialize)]
+struct AuthError {
+    error: String,
+}
+
+fn unauthorized(auth: &Auth) -> HttpResponse {
+    let error = if auth.is_configured() || auth.setup_token.lock().unwrap().is_some() {
+        "Authentication required"
+    } else {
+        "Authentication required, set AUTH_TOKEN or AUTH_USERNAME and AUTH_PASSWORD"
+    };
+    HttpResponse::Unauthorized()
+        .insert_header((header::WWW_AUTHENTICATE, "Bearer realm=\"foundry-watcher\""))
+        .json(AuthError {
+            error: error.to_string(),
+        })
+}
+
+fn forbidden(reason: &str) -> HttpResponse {
+    HttpResponse::Forbidden().json(AuthError {
+        error: format!("Forbidden: {}", reason),
+    })
+}
+
+/// Address of the client. Behind the built-in proxy that is the last address it
+/// added to `X-Forwarded-For`, earlier entries come from the client and can be forged.
+fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let peer = req.peer_addr()?.ip();
+    let behind_proxy = req
+        .app_data::<BehindBuiltinProxy>()
+        .is_some_and(|behind_proxy| behind_proxy.0);
+    if !peer.is_loopback() || !behind_proxy {
+        return Some(peer);
+    }
+    let forwarded = req
+        .headers()
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|client| client.trim().parse().ok());
+    Some(forwarded.unwrap_or(peer))
+}
+
+/// Middleware letting only authenticated requests through. Reading needs the viewer
+/// role, anything that changes state needs the operator role.
+pub async fn require_auth(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let Some(app_state) = req.app_data::<web::Data<AppState>>().cloned() else {
+        return Err(actix_web::error::ErrorInternalServerError(
+            "Application state is missing",
+        ));
+    };
+    let auth = &app_state.auth;
+    let client = client_ip(req.request());
+    let required = if matches!(*req.method(), Method::GET | Method::HEAD) {
+        Role::Viewer
+    } else {
+        Role::Operator
+    };
+
+    let session = req.cookie(SESSION_COOKIE);
+    let denial = match auth.identify(
+        req.headers(),
+        session.as_ref().map(|cookie| cookie.value()),
+        client,
+    ) {
+        Ok(Some(grant)) if grant.role() >= required => {
+            return next
+                .call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body);
+        }
+        Ok(Some(grant)) => Denial {
+            status: StatusCode::FORBIDDEN,
+            user: grant.user().map(str::to_string),
+            reason: "the operator role is required".to_string(),
+        },
+        Ok(None) => {
+            let response = unauthorized(auth);
+            return Ok(req.into_response(response).map_into_right_body());
+        }
+        Err(denial) => denial,
+    };
+
+    auth.audit(&denial, client, req.method().as_str(), req.path());
+    let response = if denial.status == StatusCode::FORBIDDEN {
+        forbidden(&denial.reason)
+    } else {
+        unauthorized(auth)
+    };
+    Ok(req.into_response(response).map_into_right_body())
+}
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    token: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+/// Exchanges a token or username and password for a session cookie
+pub async fn login(
+    req: HttpRequest,
+    payload: web::Json<LoginRequest>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    let auth = &app_state.auth;
+    let grant = match (&payload.token, &payload.username, &payload.password) {
+        (Some(token), _, _) => auth.check_token(token.trim()),
+        (None, Some(username), Some(password)) => auth.check_basic(username.trim(), password),
+        _ => None,
+    };
+
+    let Some(grant) = grant else {
+        let denial = Denial {
+            status: StatusCode::UNAUTHORIZED,
+            user: payload.username.clone(),
+            reason: "login with invalid credentials".to_string(),
+        };
+        auth.audit(&denial, client_ip(&req), "POST", req.path());
+        sleep(FAILED_LOGIN_DELAY).await;
+        return unauthorized(auth);
+    };
+
+    let id = match auth.create_session(grant) {
+        Ok(id) => id,
+        Err(e) => {
+            warn!("⚠️ Failed to create a session: {}", e);
+            return HttpResponse::InternalServerError().json(AuthError {
+                error: "Failed to create a session".to_string(),
+            });
+        }
+    };
+    let cookie = Cookie::build(SESSION_COOKIE, id)
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .secure(req.connection_info().scheme() == "https")
+        .max_age(actix_web::cookie::time::Duration::seconds(
+            SESSION_LIFETIME.as_secs() as i64,
+        ))
+        .finish();
+    HttpResponse::NoContent().cookie(cookie).finish()
+}
+
+/// Ends the browser's session
+pub async fn logout(req: HttpRequest, app_state: web::Data<AppState>) -> impl Responder {
+    if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+        app_state
+            .auth
+            .sessions
+            .lock()
+            .unwrap()
+            .remove(cookie.value());
+    }
+    let mut cookie = Cookie::build(SESSION_COOKIE, "").path("/").finish();
+    cookie.make_removal();
+    HttpResponse::NoContent().cookie(cookie).finish()
+}
+
+#[derive(Serialize)]
+struct SessionResponse<'a> {
+    grant: &'static str,
+    user: Option<&'a str>,
+    role: Role,
+}
+
+/// Tells the installer page who is signed in, or that it has to send the browser
+/// to the login page
+pub async fn session(req: HttpRequest, app_state: web::Data<AppState>) -> impl Responder {
+    let cookie = req.cookie(SESSION_COOKIE);
+    let client = client_ip(&req);
+    match app_state.auth.identify(
+        req.headers(),
+        cookie.as_ref().map(|cookie| cookie.value()),
+        client,
+    ) {
+        Ok(Some(grant)) => HttpResponse::Ok().json(SessionResponse {
+            grant: grant.kind(),
+            user: grant.user(),
+            role: grant.role(),
+        }),
+        Ok(None) => unauthorized(&app_state.auth),
+        Err(denial) => {
+            app_state.auth.audit(&denial, client, "GET", req.path());
+            if denial.status == StatusCode::FORBIDDEN {
+                forbidden(&denial.reason)
+            } else {
+                unauthorized(&app_state.auth)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::HeaderValue;
+
+    fn auth() -> Auth {
+        Auth {
+            token: Some(Secret::new("s3cret-token".to_string())),
+            basic: Some(("gm".to_string(), Secret::new("hunter2".to_string()))),
+            setup_token: Mutex::new(Some(Secret::new("setup".to_string()))),
+            sessions: Mutex::new(HashMap::new()),
+            forward_auth: None,
+            audit_log: PathBuf::new(),
+        }
+    }
+
+    fn headers(authorization: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static(authorization),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_authorize() {
+        let auth = auth();
+        assert_eq!(
+            auth.authorize(&headers("Bearer s3cret-token"), None),
+            Some(Grant::Credentials)
+        );
+        // gm:hunter2
+        assert_eq!(
+            auth.authorize(&headers("Basic Z206aHVudGVyMg=="), None),
+            Some(Grant::Credentials)
+        );
+        assert_eq!(auth.authorize(&headers("Basic Z206aHVudGVy"), None), None);
+        assert_eq!(auth.authorize(&headers("Bearer wrong"), None), None);
+        assert_eq!(auth.authorize(&HeaderMap::new(), Some("unknown")), None);
+
+        assert_eq!(
+            auth.authorize(&headers("Bearer setup"), None),
+            Some(Grant::Setup)
+        );
+        let session = auth.create_session(Grant::Setup).unwrap();
+        assert_eq!(
+            auth.authorize(&HeaderMap::new(), Some(&session)),
+            Some(Grant::Setup)
+        );
+        auth.revoke_setup_token();
+        assert_eq!(auth.authorize(&headers("Bearer setup"), None), None);
+        assert_eq!(auth.authorize(&HeaderMap::new(), Some(&session)), None);
+    }
+}
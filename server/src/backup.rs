@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+use crate::utils::files;
+
+/// Directories of the data directory that make up a backup
+const BACKUP_SOURCES: [&str; 2] = ["Config", "Data"];
+
+const BACKUP_PREFIX: &str = "foundry-backup-";
+
+/// Result of a finished backup
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupReport {
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    pub files: usize,
+}
+
+/// Archives Config and Data from `data_dir` into a timestamped zip in `backup_dir`,
+/// keeping only the newest `keep` backups. Blocks, so run it off the async runtime.
+pub fn create_backup(data_dir: &Path, backup_dir: &Path, keep: usize) -> Result<BackupReport> {
+    fs::create_dir_all(backup_dir)
+        .with_context(|| format!("Failed to create {}", backup_dir.display()))?;
+
+    let path = files::backup_path(backup_dir, BACKUP_PREFIX, ".zip");
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    // Written under a temporary name so a half-written archive is never mistaken for a backup
+    let temp = backup_dir.join(format!(".{}.tmp", name));
+
+    info!("💾 Backing up {} to {}", data_dir.display(), path.display());
+    let file =
+        File::create(&temp).with_context(|| format!("Failed to create {}", temp.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().large_file(true);
+
+    let mut files = 0;
+    for source in BACKUP_SOURCES {
+        let dir = data_dir.join(source);
+        if dir.is_dir() {
+            files += add_dir(&mut zip, &dir, source, options)?;
+        } else {
+            debug!("{} does not exist, not backing it up", dir.display());
+        }
+    }
+    zip.finish()
+        .with_context(|| format!("Failed to finish {}", temp.display()))?;
+
+    fs::rename(&temp, &path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    let size_bytes = fs::metadata(&path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+    info!("💾 Backup complete: {} files, {} bytes", files, size_bytes);
+
+    files::prune_backups(backup_dir, BACKUP_PREFIX, ".zip", keep);
+    Ok(BackupReport {
+        path,
+        size_bytes,
+        files,
+    })
+}
+
+/// Adds `dir` to the archive under `prefix`, returning the number of files added.
+/// Symlinks are skipped so a backup never leaves the data directory.
+fn add_dir(
+    zip: &mut ZipWriter<File>,
+    dir: &Path,
+    prefix: &str,
+    options: SimpleFileOptions,
+) -> Result<usize> {
+    zip.add_directory(prefix, options)
+        .with_context(|| format!("Failed to add {}", dir.display()))?;
+
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut files = 0;
+    for entry in entries {
+        let path = entry.path();
+        let name = format!("{}/{}", prefix, entry.file_name().to_string_lossy());
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if file_type.is_dir() {
+            files += add_dir(zip, &path, &name, options)?;
+        } else if file_type.is_file() {
+            let mut source = match File::open(&path) {
+                Ok(source) => source,
+                Err(e) => {
+                    // Files can disappear while Foundry is running
+                    warn!("⚠️ Skipping {} in backup: {}", path.display(), e);
+                    continue;
+                }
+            };
+            zip.start_file(name, options)
+                .with_context(|| format!("Failed to add {}", path.display()))?;
+            io::copy(&mut source, zip)
+                .with_context(|| format!("Failed to back up {}", path.display()))?;
+            files += 1;
+        } else {
+            debug!("Skipping {} in backup, not a regular file", path.display());
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_create_backup() {
+        let root = std::env::temp_dir().join(format!("backup-test-{}", std::process::id()));
+        let data_dir = root.join("data");
+        fs::create_dir_all(data_dir.join("Config")).unwrap();
+        fs::create_dir_all(data_dir.join("Data/worlds/sandbox")).unwrap();
+        fs::create_dir_all(data_dir.join("Logs")).unwrap();
+        fs::write(data_dir.join("Config/options.json"), "{}").unwrap();
+        fs::write(data_dir.join("Data/worlds/sandbox/world.json"), "{}").unwrap();
+        fs::write(data_dir.join("Logs/debug.log"), "noise").unwrap();
+
+        let report = create_backup(&data_dir, &root.join("backups"), 1).unwrap();
+        let mut archive = ZipArchive::new(File::open(&report.path).unwrap()).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(report.files, 2);
+        assert!(names.contains(&"Config/options.json"));
+        assert!(names.contains(&"Data/worlds/sandbox/world.json"));
+        assert!(!names.iter().any(|name| name.starts_with("Logs")));
+        assert!(archive.by_name("Config/options.json").is_ok());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
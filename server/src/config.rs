@@ -1,13 +1,53 @@
+use crate::hooks::{HookSet, HooksConfig};
+use crate::limits::ResourceLimits;
+use crate::options::{self, FoundryOptions, OptionsPrecedence};
+use crate::privileges::{self, RunAs};
+use crate::schedule::{self, PlayWindow, Schedules};
+use crate::tls::TlsSource;
 use crate::utils::paths;
+use crate::watchdog::WatchdogConfig;
 use std::env;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
 pub struct AppConfig {
     pub static_files_dir: String,
     pub server_port: u16,
     pub server_host: String,
+    /// Port Foundry itself listens on
+    pub foundry_port: u16,
+    /// The watcher keeps the public port and proxies to Foundry on `foundry_port`
+    pub builtin_proxy: bool,
+    /// Port of the management API, which stays up for the life of the container
+    pub management_port: u16,
     pub target_dir: String,
-    pub foundry_args: Vec<String>,
+    pub foundry_options: FoundryOptions,
+    /// How `foundry_options` is reconciled with Config/options.json
+    pub options_precedence: OptionsPrecedence,
     pub foundry_script: String,
+    pub node_binary: String,
+    pub use_npx: bool,
+    /// Flags passed to node before the Foundry script
+    pub node_flags: Vec<String>,
+    pub resource_limits: ResourceLimits,
+    /// Account Foundry is started as, only set when the watcher runs as root
+    pub run_as: Option<RunAs>,
+    pub log_buffer_lines: usize,
+    pub readiness_interval_secs: u64,
+    pub watchdog: WatchdogConfig,
+    pub schedules: Schedules,
+    pub hooks: HooksConfig,
+    /// Where backups of the data directory are written
+    pub backup_dir: PathBuf,
+    /// Number of backups kept in `backup_dir`
+    pub backup_keep: usize,
+    /// Consecutive runs that end before Foundry listens after which the installer
+    /// comes back, 0 to keep retrying forever
+    pub startup_failure_limit: u32,
+    /// Certificate for HTTPS on the management API and the built-in proxy
+    pub tls: Option<TlsSource>,
 }
 
 impl AppConfig {
@@ -25,28 +65,242 @@ impl AppConfig {
 
         let target_dir = get_target_directory();
 
+        // Behind the built-in proxy Foundry listens on an internal port. Without it,
+        // Foundry takes over the installer's port once it is installed.
+        let builtin_proxy = env_flag("BUILTIN_PROXY", true);
+        let foundry_port = if builtin_proxy {
+            env_parse("FOUNDRY_INTERNAL_PORT", 30000)
+        } else {
+            server_port
+        };
+
+        let management_port = env_parse("MANAGEMENT_PORT", 4445);
+
         let foundry_host =
             env::var("APPLICATION_HOST").unwrap_or("foundry.vtt".to_string());
 
-        let foundry_args = vec![
-            format!("--dataPath={}", *paths::DATA_DIR),
-            format!("--port={}", server_port),
-            format!("--hostname={}", foundry_host),
-            "--noupnp".to_string(),
-            "--proxySSL".to_string(),
-        ];
+        // Invalid combinations are rejected by `initialization::validate_env`
+        let tls = match (env_string("TLS_CERT"), env_string("TLS_KEY")) {
+            (Some(cert), Some(key)) => Some(TlsSource::Files {
+                cert: PathBuf::from(cert),
+                key: PathBuf::from(key),
+            }),
+            _ if env_flag("TLS_SELF_SIGNED", false) => Some(TlsSource::SelfSigned {
+                hostname: foundry_host.clone(),
+                dir: Path::new(&*paths::DATA_DIR)
+                    .join("Config")
+                    .join("watcher-tls"),
+            }),
+            _ => None,
+        };
+
+        // Invalid combinations are rejected during initialization
+        let foundry_options = FoundryOptions {
+            data_path: paths::DATA_DIR.to_string(),
+            port: foundry_port,
+            hostname: Some(foundry_host),
+            // The built-in proxy terminating TLS is a proxy handling SSL as far as Foundry knows
+            proxy_ssl: env_flag("SSL_PROXY", false) || (builtin_proxy && tls.is_some()),
+            // Invitation links point at the public port rather than the internal one
+            proxy_port: env_opt("PROXY_PORT").or(builtin_proxy.then_some(server_port)),
+            route_prefix: env::var("ROUTE_PREFIX")
+                .ok()
+                .and_then(|prefix| options::normalize_route_prefix(&prefix)),
+            ssl_cert: env_string("SSL_CERT"),
+            ssl_key: env_string("SSL_KEY"),
+            language: env_string("LANGUAGE"),
+            world: env_string("FOUNDRY_WORLD"),
+            upnp: env_flag("UPNP", false),
+            compress_static: env_opt("COMPRESS_STATIC"),
+            compress_socket: env_opt("COMPRESS_SOCKET"),
+            extra_args: env_words("FOUNDRY_EXTRA_ARGS"),
+        };
+
+        let options_precedence = env_parse("OPTIONS_JSON_PRECEDENCE", OptionsPrecedence::Env);
 
         let foundry_script = paths::FOUNDRY_SCRIPT_PATH.to_string_lossy().to_string();
 
+        let node_binary = resolve_node_binary();
+
+        let use_npx = env_flag("FOUNDRY_USE_NPX", false);
+
+        let mut node_flags = Vec::new();
+        if let Some(heap_mb) = env_opt::<u64>("NODE_MAX_OLD_SPACE_SIZE") {
+            node_flags.push(format!("--max-old-space-size={}", heap_mb));
+        }
+        node_flags.extend(env_words("NODE_EXTRA_FLAGS"));
+
+        const MB: u64 = 1024 * 1024;
+        let resource_limits = ResourceLimits {
+            address_space: env_opt::<u64>("FOUNDRY_LIMIT_AS_MB").map(|mb| mb * MB),
+            open_files: env_opt("FOUNDRY_LIMIT_NOFILE"),
+            core_size: env_opt::<u64>("FOUNDRY_LIMIT_CORE_MB").map(|mb| mb * MB),
+        };
+
+        // Defaults to the image's node user; PUID=0 keeps Foundry running as root
+        let run_as = Some(RunAs {
+            uid: env_parse("PUID", 1000),
+            gid: env_parse("PGID", 1000),
+        })
+        .filter(|run_as| privileges::is_root() && run_as.uid != 0);
+
+        let log_buffer_lines = env_parse("LOG_BUFFER_LINES", 1000);
+
+        let readiness_interval_secs = env_parse("READINESS_INTERVAL_SECS", 5).max(1);
+
+        let watchdog = WatchdogConfig::from_env();
+
+        // Invalid expressions are rejected by `initialization::validate_env`
+        let schedules = Schedules {
+            restart: env::var("RESTART_SCHEDULE")
+                .ok()
+                .and_then(|expr| schedule::parse_cron(&expr).ok()),
+            play_window: env::var("PLAY_WINDOW_START")
+                .ok()
+                .and_then(|expr| schedule::parse_cron(&expr).ok())
+                .map(|start| PlayWindow {
+                    start,
+                    duration: env::var("PLAY_WINDOW_DURATION")
+                        .ok()
+                        .and_then(|value| schedule::parse_duration(&value).ok())
+                        .unwrap_or_else(|| chrono::Duration::hours(6)),
+                }),
+        };
+
+        let hooks = HooksConfig {
+            pre_launch: hook_set(
+                "PRE_LAUNCH_HOOK_DIR",
+                "/hooks/pre-launch.d",
+                "PRE_LAUNCH_HOOK",
+            ),
+            post_exit: hook_set("POST_EXIT_HOOK_DIR", "/hooks/post-exit.d", "POST_EXIT_HOOK"),
+            timeout: Duration::from_secs(env_parse("HOOK_TIMEOUT_SECS", 60).max(1)),
+            block_on_failure: env_flag("PRE_LAUNCH_HOOK_BLOCKING", true),
+        };
+
+        let backup_dir = env::var("BACKUP_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| Path::new(&*paths::DATA_DIR).join("Backups").join("watcher"));
+
+        let backup_keep = env_parse("BACKUP_KEEP", 5).max(1);
+
+        let startup_failure_limit = env_parse("STARTUP_FAILURE_LIMIT", 3);
+
         Self {
             static_files_dir,
             server_port,
             server_host,
+            foundry_port,
+            builtin_proxy,
+            management_port,
             target_dir,
-            foundry_args,
+            foundry_options,
+            options_precedence,
             foundry_script,
+            node_binary,
+            use_npx,
+            node_flags,
+            resource_limits,
+            run_as,
+            log_buffer_lines,
+            readiness_interval_secs,
+            watchdog,
+            schedules,
+            hooks,
+            backup_dir,
+            backup_keep,
+            startup_failure_limit,
+            tls,
         }
     }
+
+    /// Full command line used to start Foundry, program first
+    pub fn foundry_command(&self) -> Vec<String> {
+        let mut command = if self.use_npx {
+            vec!["npx".to_string(), "--yes".to_string(), "node".to_string()]
+        } else {
+            vec![self.node_binary.clone()]
+        };
+        command.extend(self.node_flags.iter().cloned());
+        command.push(self.foundry_script.clone());
+        command.extend(self.foundry_options.args());
+        command
+    }
+}
+
+/// Reads a boolean flag, accepting `true`/`false` case-insensitively
+pub(crate) fn env_flag(name: &str, default: bool) -> bool {
+    env::var(name)
+        .map(|v| v.trim().eq_ignore_ascii_case("true"))
+        .unwrap_or(default)
+}
+
+/// Parses an environment variable, falling back to `default` when unset. Invalid
+/// values are rejected at startup by `initialization::validate_env`.
+pub(crate) fn env_parse<T: FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.trim().parse::<T>().ok())
+        .unwrap_or(default)
+}
+
+/// Parses an optional environment variable, treating invalid values as unset
+pub(crate) fn env_opt<T: FromStr>(name: &str) -> Option<T> {
+    env::var(name).ok().and_then(|v| v.trim().parse::<T>().ok())
+}
+
+/// Reads a non-empty, trimmed string variable
+pub(crate) fn env_string(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Splits a variable into arguments using shell quoting rules; unbalanced quotes yield nothing
+pub(crate) fn env_words(name: &str) -> Vec<String> {
+    env::var(name)
+        .ok()
+        .and_then(|v| shell_words::split(&v).ok())
+        .unwrap_or_default()
+}
+
+/// Hook directory and command for one lifecycle event; an empty command is ignored
+fn hook_set(dir_var: &str, default_dir: &str, command_var: &str) -> HookSet {
+    HookSet {
+        dir: PathBuf::from(env::var(dir_var).unwrap_or_else(|_| default_dir.to_string())),
+        command: env::var(command_var)
+            .ok()
+            .filter(|command| !command.trim().is_empty()),
+    }
+}
+
+/// Resolves the node executable from NODE_BINARY, falling back to a PATH lookup
+fn resolve_node_binary() -> String {
+    let binary = env::var("NODE_BINARY").unwrap_or_else(|_| "node".to_string());
+
+    // Explicit paths are used as-is and validated at startup
+    if binary.contains('/') {
+        return binary;
+    }
+
+    find_on_path(&binary)
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or(binary)
+}
+
+/// Searches the PATH environment variable for an executable named `name`
+pub(crate) fn find_on_path(name: &str) -> Option<PathBuf> {
+    let path_var = env::var_os("PATH")?;
+    env::split_paths(&path_var)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+pub(crate) fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
 }
 
 pub(crate) fn get_target_directory() -> String {
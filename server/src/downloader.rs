@@ -1,7 +1,9 @@
 use crate::events::ProgressEvent;
+use crate::metrics::Metrics;
 use reqwest::Client;
 use tokio::fs;
 use tokio::sync::broadcast;
+use tokio::time::Instant;
 use tracing::{debug, error, info};
 
 pub struct DownloadService;
@@ -12,8 +14,10 @@ impl DownloadService {
         url: &str,
         save_path: &str,
         event_tx: broadcast::Sender<ProgressEvent>,
+        metrics: &Metrics,
     ) -> Result<(), actix_web::Error> {
         info!("Starting download from URL: {}", url);
+        let started = Instant::now();
 
         let client = Client::new();
         let mut resp = client.get(url).send().await.map_err(|e| {
@@ -56,13 +60,18 @@ impl DownloadService {
 
         // Use a buffer to track download progress
         let mut downloaded: u64 = 0;
-        while let Some(chunk) = resp.chunk().await.map_err(|e| {
-            error!("Failed reading download stream: {}", e);
-            actix_web::error::ErrorInternalServerError(format!(
-                "Failed reading download stream: {}",
-                e
-            ))
-        })? {
+        loop {
+            // Bound first, so the error type is not held across the awaits below
+            let chunk = resp.chunk().await.map_err(|e| {
+                error!("Failed reading download stream: {}", e);
+                actix_web::error::ErrorInternalServerError(format!(
+                    "Failed reading download stream: {}",
+                    e
+                ))
+            })?;
+            let Some(chunk) = chunk else {
+                break;
+            };
             use tokio::io::AsyncWriteExt;
             out.write_all(&chunk).await.map_err(|e| {
                 error!("Failed to write file: {}", e);
@@ -111,6 +120,7 @@ impl DownloadService {
         }
 
         info!("Download completed successfully: {} bytes", downloaded);
+        metrics.record_download(downloaded, started.elapsed());
         Ok(())
     }
 }
@@ -3,8 +3,9 @@ use bytes::Bytes;
 use futures_util::stream::{self};
 use serde::Serialize;
 use std::time::Duration;
+use tokio::sync::broadcast;
 use tokio::time::interval;
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
 use crate::server::AppState;
 
@@ -13,6 +14,9 @@ pub struct ProgressEvent {
     pub event_type: String,
     pub message: String,
     pub progress: Option<f32>,
+    /// Install job the event belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<u64>,
 }
 
 impl ProgressEvent {
@@ -21,6 +25,7 @@ impl ProgressEvent {
             event_type: event_type.to_string(),
             message: message.to_string(),
             progress,
+            job_id: None,
         }
     }
 
@@ -33,30 +38,89 @@ impl ProgressEvent {
     }
 }
 
+/// A client of the event stream, counted for as long as it stays connected
+struct Subscription {
+    rx: broadcast::Receiver<ProgressEvent>,
+    app_state: web::Data<AppState>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.app_state.metrics.sse_disconnected();
+    }
+}
+
+/// Turns an event into what anonymous visitors of the maintenance page may see.
+/// Only supervisor lifecycle events pass. Install and upgrade phases get a fixed
+/// message, since theirs can name download URLs or paths. Everything else is dropped.
+fn public_event(event: ProgressEvent) -> Option<ProgressEvent> {
+    let message = match event.event_type.as_str() {
+        "restart" | "schedule" | "watchdog" | "oom" | "readiness" => event.message,
+        "upgrade" => "FoundryVTT is being updated".to_string(),
+        "start" | "downloading" | "downloaded" | "uploading" | "uploaded" | "extracting"
+        | "extracted" | "cleanup" | "complete" | "transition" => {
+            "Installing FoundryVTT".to_string()
+        }
+        _ => return None,
+    };
+    Some(ProgressEvent {
+        message,
+        job_id: None,
+        ..event
+    })
+}
+
+/// Every event, for the installer and the management API
 pub async fn sse_events(data: web::Data<AppState>) -> HttpResponse {
     debug!("Client connected to SSE events endpoint");
-    let rx = data.event_channel.subscribe();
+    stream_events(data, Some)
+}
+
+/// Restart and upgrade progress without details, open to anyone like `/readyz`
+pub async fn sse_public_progress(data: web::Data<AppState>) -> HttpResponse {
+    debug!("Client connected to SSE progress endpoint");
+    stream_events(data, public_event)
+}
+
+fn stream_events(
+    data: web::Data<AppState>,
+    filter: fn(ProgressEvent) -> Option<ProgressEvent>,
+) -> HttpResponse {
+    data.metrics.sse_connected();
+    let subscription = Subscription {
+        rx: data.event_channel.subscribe(),
+        app_state: data.clone(),
+    };
 
     // Create a stream that combines events with keepalive pings
-    // We need to wrap the state in Arc<Mutex<>> to avoid ownership issues
     let stream = stream::unfold(
-        (rx, interval(Duration::from_secs(15))),
-        |(mut rx, mut keepalive)| async move {
-            tokio::select! {
-                _ = keepalive.tick() => {
-                    debug!("Sending SSE keepalive");
-                    Some((Ok::<_, Error>(Bytes::from(":\n\n")), (rx, keepalive)))
-                }
-                event = rx.recv() => {
-                    match event {
-                        Ok(progress_event) => {
-                            debug!("Sending event: {:?}", progress_event);
-                            let bytes = Bytes::from(progress_event.to_sse_format());
-                            Some((Ok::<_, Error>(bytes), (rx, keepalive)))
-                        }
-                        Err(e) => {
-                            error!("SSE channel error: {}", e);
-                            None
+        (subscription, interval(Duration::from_secs(15))),
+        move |(mut subscription, mut keepalive)| async move {
+            loop {
+                tokio::select! {
+                    _ = keepalive.tick() => {
+                        debug!("Sending SSE keepalive");
+                        return Some((Ok::<_, Error>(Bytes::from(":\n\n")), (subscription, keepalive)));
+                    }
+                    event = subscription.rx.recv() => {
+                        match event {
+                            Ok(progress_event) => {
+                                let Some(progress_event) = filter(progress_event) else {
+                                    continue;
+                                };
+                                debug!("Sending event: {:?}", progress_event);
+                                let bytes = Bytes::from(progress_event.to_sse_format());
+                                return Some((Ok::<_, Error>(bytes), (subscription, keepalive)));
+                            }
+                            // A slow client misses the oldest events but keeps receiving new ones
+                            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                                warn!("Event subscriber lagged, skipped {} events", skipped);
+                                subscription.app_state.metrics.record_dropped_events(skipped);
+                            }
+                            Err(e) => {
+                                error!("SSE channel error: {}", e);
+                                return None;
+                            }
                         }
                     }
                 }
@@ -70,3 +134,33 @@ pub async fn sse_events(data: web::Data<AppState>) -> HttpResponse {
         .insert_header(("Connection", "keep-alive"))
         .streaming(stream)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_event_hides_details() {
+        let mut upgrade = ProgressEvent::new(
+            "upgrade",
+            "Downloading FoundryVTT update from https://example.com/foundry.zip?token=secret",
+            Some(0.0),
+        );
+        upgrade.job_id = Some(3);
+        let public = public_event(upgrade).unwrap();
+        assert_eq!(public.message, "FoundryVTT is being updated");
+        assert_eq!(public.progress, Some(0.0));
+        assert_eq!(public.job_id, None);
+
+        let restart = ProgressEvent::new("restart", "FoundryVTT is restarting", None);
+        assert_eq!(
+            public_event(restart).unwrap().message,
+            "FoundryVTT is restarting"
+        );
+
+        for event_type in ["error", "hook", "world", "port"] {
+            let event = ProgressEvent::new(event_type, "/foundrydata/Data/worlds", None);
+            assert!(public_event(event).is_none(), "{}", event_type);
+        }
+    }
+}
@@ -1,10 +1,12 @@
 use crate::events::ProgressEvent;
+use crate::metrics::Metrics;
 use std::fs::File;
 use std::path::Path;
 use std::sync::Arc;
 use tokio::fs;
 use tokio::sync::broadcast;
 use tokio::task;
+use tokio::time::Instant;
 use tracing::{debug, error, info, warn};
 use zip::read::ZipArchive;
 
@@ -16,8 +18,10 @@ impl ExtractorService {
         archive_path: String,
         target_directory: String,
         event_tx: broadcast::Sender<ProgressEvent>,
+        metrics: &Metrics,
     ) -> Result<(), std::io::Error> {
         info!("Starting extraction of archive: {}", archive_path);
+        let started = Instant::now();
 
         // Verify file exists before attempting extraction
         if !Path::new(&archive_path).exists() {
@@ -120,10 +124,7 @@ impl ExtractorService {
                         None,
                     ));
                     // Convert ZipError to std::io::Error
-                    Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("Extraction failed: {}", e),
-                    ))
+                    Err(std::io::Error::other(format!("Extraction failed: {}", e)))
                 }
             }
         })
@@ -135,16 +136,17 @@ impl ExtractorService {
                 &format!("Extraction thread panicked: {}", e),
                 None,
             ));
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Extraction thread panicked: {}", e),
-            ))
+            Err(std::io::Error::other(format!(
+                "Extraction thread panicked: {}",
+                e
+            )))
         })?;
 
         info!(
             "Extraction completed successfully to {}",
             target_directory_clone
         );
+        metrics.record_extraction(started.elapsed());
         Ok(())
     }
 }
@@ -0,0 +1,254 @@
+use actix_web::http::header::{HeaderMap, HeaderName};
+use std::env;
+use std::net::IpAddr;
+use std::str::FromStr;
+use tracing::warn;
+
+use crate::auth::Role;
+
+/// Address range a forward-auth proxy connects from, e.g. `172.16.0.0/12`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                prefix_matches(&network.octets(), &ip.octets(), self.prefix)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                prefix_matches(&network.octets(), &ip.octets(), self.prefix)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    /// Parses `address/prefix`, or a bare address meaning just that host
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let (address, prefix) = match value.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (value, None),
+        };
+        let network: IpAddr = address
+            .parse()
+            .map_err(|_| format!("invalid address in {}", value))?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= max)
+                .ok_or_else(|| format!("invalid prefix length in {}", value))?,
+            None => max,
+        };
+        Ok(Self {
+            network: network.to_canonical(),
+            prefix,
+        })
+    }
+}
+
+fn prefix_matches(network: &[u8], ip: &[u8], prefix: u8) -> bool {
+    let full_bytes = usize::from(prefix / 8);
+    let remaining_bits = prefix % 8;
+    if network[..full_bytes] != ip[..full_bytes] {
+        return false;
+    }
+    if remaining_bits == 0 {
+        return true;
+    }
+    let mask = 0xffu8 << (8 - remaining_bits);
+    network[full_bytes] & mask == ip[full_bytes] & mask
+}
+
+/// Parses a comma-separated list of CIDRs
+pub fn parse_cidrs(value: &str) -> Result<Vec<Cidr>, String> {
+    value
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Identity asserted by an authenticating proxy in front of the watcher
+pub struct ForwardAuth {
+    header: HeaderName,
+    trusted_proxies: Vec<Cidr>,
+    operators: Vec<String>,
+    viewers: Vec<String>,
+}
+
+/// What a forwarded identity amounts to
+#[derive(Debug, PartialEq)]
+pub enum ForwardedIdentity {
+    /// No user header on the request
+    Absent,
+    /// The header came from an address that is not a trusted proxy
+    Untrusted {
+        user: String,
+    },
+    /// A trusted proxy vouched for a user who has no role
+    NoRole {
+        user: String,
+    },
+    Granted {
+        user: String,
+        role: Role,
+    },
+}
+
+impl ForwardAuth {
+    /// Reads the `FORWARD_AUTH_*` variables, `None` unless a user header is configured.
+    /// Invalid ranges are rejected by `initialization::validate_env`.
+    pub fn from_env() -> Option<Self> {
+        let header = env::var("FORWARD_AUTH_HEADER").ok()?;
+        let header = match HeaderName::from_bytes(header.trim().as_bytes()) {
+            Ok(header) => header,
+            Err(_) => {
+                warn!(
+                    "⚠️ FORWARD_AUTH_HEADER is not a valid header name: {}",
+                    header
+                );
+                return None;
+            }
+        };
+        let trusted_proxies = env::var("FORWARD_AUTH_TRUSTED_PROXIES")
+            .ok()
+            .and_then(|value| parse_cidrs(&value).ok())
+            .unwrap_or_default();
+        if trusted_proxies.is_empty() {
+            warn!(
+                "⚠️ FORWARD_AUTH_HEADER is set without FORWARD_AUTH_TRUSTED_PROXIES, the header is never trusted"
+            );
+        }
+
+        Some(Self {
+            header,
+            trusted_proxies,
+            operators: user_list("FORWARD_AUTH_OPERATORS"),
+            viewers: user_list("FORWARD_AUTH_VIEWERS"),
+        })
+    }
+
+    /// Operators first, so a user in both lists gets the larger role. `*` matches every user.
+    pub fn role(&self, user: &str) -> Option<Role> {
+        let listed = |users: &[String]| users.iter().any(|entry| entry == "*" || entry == user);
+        if listed(&self.operators) {
+            Some(Role::Operator)
+        } else if listed(&self.viewers) {
+            Some(Role::Viewer)
+        } else {
+            None
+        }
+    }
+
+    /// Reads the user header of a request from `client`
+    pub fn identify(&self, headers: &HeaderMap, client: Option<IpAddr>) -> ForwardedIdentity {
+        let Some(user) = headers
+            .get(&self.header)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|user| !user.is_empty())
+            .map(str::to_string)
+        else {
+            return ForwardedIdentity::Absent;
+        };
+
+        let trusted = client.is_some_and(|client| {
+            self.trusted_proxies
+                .iter()
+                .any(|cidr| cidr.contains(client))
+        });
+        if !trusted {
+            return ForwardedIdentity::Untrusted { user };
+        }
+        match self.role(&user) {
+            Some(role) => ForwardedIdentity::Granted { user, role },
+            None => ForwardedIdentity::NoRole { user },
+        }
+    }
+}
+
+fn user_list(name: &str) -> Vec<String> {
+    env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(|user| user.trim().to_string())
+        .filter(|user| !user.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::HeaderValue;
+
+    #[test]
+    fn test_cidr_contains() {
+        let private: Cidr = "172.16.0.0/12".parse().unwrap();
+        assert!(private.contains("172.20.1.9".parse().unwrap()));
+        assert!(!private.contains("172.32.0.1".parse().unwrap()));
+        // IPv4 clients show up mapped into IPv6 on dual-stack sockets
+        assert!(private.contains("::ffff:172.20.1.9".parse().unwrap()));
+
+        let host: Cidr = "10.0.0.5".parse().unwrap();
+        assert!(host.contains("10.0.0.5".parse().unwrap()));
+        assert!(!host.contains("10.0.0.6".parse().unwrap()));
+
+        let v6: Cidr = "fd00::/8".parse().unwrap();
+        assert!(v6.contains("fd12::1".parse().unwrap()));
+        assert!(!v6.contains("10.0.0.5".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!(parse_cidrs("10.0.0.0/8, nonsense").is_err());
+    }
+
+    #[test]
+    fn test_identify() {
+        let forward_auth = ForwardAuth {
+            header: HeaderName::from_static("x-forwarded-user"),
+            trusted_proxies: parse_cidrs("10.0.0.0/8").unwrap(),
+            operators: vec!["alice".to_string()],
+            viewers: vec!["*".to_string()],
+        };
+        let user = HeaderName::from_static("x-forwarded-user");
+        let mut headers = HeaderMap::new();
+        let proxy = Some("10.1.2.3".parse().unwrap());
+        assert_eq!(
+            forward_auth.identify(&headers, proxy),
+            ForwardedIdentity::Absent
+        );
+
+        headers.insert(user.clone(), HeaderValue::from_static("alice"));
+        assert_eq!(
+            forward_auth.identify(&headers, proxy),
+            ForwardedIdentity::Granted {
+                user: "alice".to_string(),
+                role: Role::Operator
+            }
+        );
+        assert_eq!(
+            forward_auth.identify(&headers, Some("203.0.113.9".parse().unwrap())),
+            ForwardedIdentity::Untrusted {
+                user: "alice".to_string()
+            }
+        );
+
+        headers.insert(user.clone(), HeaderValue::from_static("bob"));
+        assert_eq!(
+            forward_auth.identify(&headers, proxy),
+            ForwardedIdentity::Granted {
+                user: "bob".to_string(),
+                role: Role::Viewer
+            }
+        );
+    }
+}
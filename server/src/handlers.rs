@@ -1,15 +1,28 @@
+use crate::backup;
 use crate::downloader::DownloadService;
 use crate::events::ProgressEvent;
 use crate::extractor::ExtractorService;
+use crate::install_check;
+use crate::jobs::{self, InstallKind, JobHandle, JobState};
+use crate::launch::{SupervisorCommand, SupervisorStatus};
+use crate::metrics::Metrics;
+use crate::readiness::ReadinessSnapshot;
 use crate::server::AppState;
+use crate::upgrade;
+use crate::utils::paths;
 use actix_multipart::Multipart;
+use actix_web::http::{StatusCode, header};
 use actix_web::{HttpResponse, Responder, web};
+use chrono::{DateTime, Utc};
 use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
+use std::sync::atomic::Ordering;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
 use tracing::{debug, error, info, warn};
 
 #[derive(Deserialize)]
@@ -27,15 +40,295 @@ pub struct ErrorResponse {
     error: String,
 }
 
-pub async fn info() -> impl Responder {
+/// Answer to a request that started an install job, which `/api/v1/installs/{id}` reports on
+#[derive(Serialize)]
+pub struct JobAccepted {
+    id: u64,
+    message: String,
+}
+
+#[derive(Serialize)]
+pub struct InfoResponse {
+    message: String,
+    supervisor: SupervisorStatus,
+    readiness: ReadinessSnapshot,
+    /// Set while the installer is back because the installation is broken
+    install_failure: Option<String>,
+}
+
+pub async fn info(app_state: web::Data<AppState>) -> impl Responder {
     // This endpoint can be used to check the server status or provide information
-    HttpResponse::Ok().json(SuccessResponse {
+    HttpResponse::Ok().json(InfoResponse {
         message: "Server is running".to_string(),
+        supervisor: app_state.supervisor.lock().unwrap().clone(),
+        readiness: app_state.readiness.snapshot(),
+        install_failure: app_state.install_failure.lock().unwrap().clone(),
+    })
+}
+
+#[derive(Serialize)]
+pub struct ScheduleResponse {
+    scheduled_off_until: Option<DateTime<Utc>>,
+    next_restart: Option<DateTime<Utc>>,
+}
+
+pub async fn schedule_info(app_state: web::Data<AppState>) -> impl Responder {
+    let supervisor = app_state.supervisor.lock().unwrap();
+    HttpResponse::Ok().json(ScheduleResponse {
+        scheduled_off_until: supervisor.scheduled_off_until,
+        next_restart: supervisor.next_restart,
+    })
+}
+
+#[derive(Serialize)]
+pub struct InstallStatusResponse {
+    /// Why the installer is showing although FoundryVTT is installed
+    failure: Option<String>,
+}
+
+/// Lets the installer explain why it is back
+pub async fn install_status(app_state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(InstallStatusResponse {
+        failure: app_state.install_failure.lock().unwrap().clone(),
+    })
+}
+
+/// Answers every request with the scheduled-off page while Foundry is stopped by schedule
+pub async fn offline_page(page_path: String, app_state: web::Data<AppState>) -> HttpResponse {
+    let back_at = app_state.supervisor.lock().unwrap().scheduled_off_until;
+    let mut response = HttpResponse::ServiceUnavailable();
+    if let Some(back_at) = back_at {
+        let retry_after = (back_at - Utc::now()).num_seconds().max(0);
+        response.insert_header(("Retry-After", retry_after.to_string()));
+    }
+
+    match fs::read_to_string(&page_path).await {
+        Ok(page) => response.content_type("text/html; charset=utf-8").body(page),
+        Err(e) => {
+            warn!("Failed to read {}: {}", page_path, e);
+            response
+                .content_type("text/plain; charset=utf-8")
+                .body(match back_at {
+                    Some(at) => format!("Foundry VTT is scheduled off until {}", at.to_rfc3339()),
+                    None => "Foundry VTT is scheduled off".to_string(),
+                })
+        }
+    }
+}
+
+/// Asks the supervisor to restart the running Foundry process
+pub async fn restart(app_state: web::Data<AppState>) -> impl Responder {
+    if app_state.supervisor.lock().unwrap().pid.is_none() {
+        return HttpResponse::Conflict().json(ErrorResponse {
+            error: "FoundryVTT is not running".to_string(),
+        });
+    }
+
+    info!("Restart of FoundryVTT requested through the management API");
+    if app_state
+        .commands
+        .send(SupervisorCommand::Restart)
+        .await
+        .is_err()
+    {
+        return HttpResponse::ServiceUnavailable().json(ErrorResponse {
+            error: "The FoundryVTT supervisor is not running".to_string(),
+        });
+    }
+
+    HttpResponse::Accepted().json(SuccessResponse {
+        message: "FoundryVTT is restarting".to_string(),
+    })
+}
+
+#[derive(Deserialize)]
+pub struct RecentLogsQuery {
+    lines: Option<usize>,
+}
+
+/// Returns the most recent Foundry output from the log buffer
+pub async fn recent_logs(
+    app_state: web::Data<AppState>,
+    query: web::Query<RecentLogsQuery>,
+) -> impl Responder {
+    HttpResponse::Ok().json(app_state.logs.tail(query.lines.unwrap_or(200)))
+}
+
+/// Starts downloading a new Foundry release and answers right away with the job's
+/// ID. The supervisor then stops Foundry, installs the update and starts it again.
+pub async fn upgrade(
+    url_payload: web::Json<UrlPayload>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    if !paths::FOUNDRY_SCRIPT_PATH.exists() {
+        return HttpResponse::Conflict().json(ErrorResponse {
+            error: "FoundryVTT is not installed yet, use the installer instead".to_string(),
+        });
+    }
+    if app_state.commands.is_closed() {
+        return HttpResponse::ServiceUnavailable().json(ErrorResponse {
+            error: "The FoundryVTT supervisor is not running".to_string(),
+        });
+    }
+    if app_state.upgrading.swap(true, Ordering::SeqCst) {
+        return HttpResponse::Conflict().json(ErrorResponse {
+            error: "An update is already in progress".to_string(),
+        });
+    }
+
+    let url = url_payload.url.clone();
+    let Some(job) = jobs::start(&app_state, InstallKind::Upgrade, &url) else {
+        app_state.upgrading.store(false, Ordering::SeqCst);
+        return HttpResponse::Conflict().json(ErrorResponse {
+            error: "An installation is already in progress".to_string(),
+        });
+    };
+    info!("Received request to upgrade FoundryVTT from URL: {}", url);
+
+    let id = job.id;
+    tokio::spawn(download_upgrade(url, app_state, job));
+    HttpResponse::Accepted()
+        .insert_header((header::LOCATION, format!("/api/v1/installs/{}", id)))
+        .json(JobAccepted {
+            id,
+            message: "Downloading the update, FoundryVTT will restart to install it".to_string(),
+        })
+}
+
+/// Downloads the update for `job` and hands it to the supervisor, which finishes the
+/// job once the update is installed
+async fn download_upgrade(url: String, app_state: web::Data<AppState>, job: JobHandle) {
+    let _ = job.events.send(ProgressEvent::new(
+        "upgrade",
+        &format!("Downloading FoundryVTT update from {}", url),
+        Some(0.0),
+    ));
+
+    let archive_path = upgrade::archive_path(&crate::config::get_target_directory());
+    let archive = archive_path.to_string_lossy().to_string();
+    // The download error is turned into text here, because actix errors cannot be
+    // held across an await in a spawned task
+    let downloaded = DownloadService::download_file_from_url(
+        &url,
+        &archive,
+        job.events.clone(),
+        &app_state.metrics,
+    )
+    .await
+    .map_err(|e| format!("Failed to download file: {}", e));
+    let verified = match downloaded {
+        Ok(()) => match fs::metadata(&archive_path).await {
+            Ok(metadata) if metadata.len() > 0 => Ok(()),
+            Ok(_) => Err("Downloaded file is empty".to_string()),
+            Err(e) => Err(format!("Failed to verify downloaded file: {}", e)),
+        },
+        Err(message) => Err(message),
+    };
+    if let Err(message) = verified {
+        error!("{}", message);
+        let _ = fs::remove_file(&archive_path).await;
+        let _ = job.events.send(ProgressEvent::new("error", &message, None));
+        job.finish(JobState::Failed).await;
+        app_state.upgrading.store(false, Ordering::SeqCst);
+        return;
+    }
+
+    let command = SupervisorCommand::Upgrade {
+        archive: archive_path,
+        job,
+    };
+    if let Err(mpsc::error::SendError(SupervisorCommand::Upgrade { archive, job })) =
+        app_state.commands.send(command).await
+    {
+        let message = "The FoundryVTT supervisor is not running";
+        error!("{}", message);
+        let _ = fs::remove_file(&archive).await;
+        let _ = job.events.send(ProgressEvent::new("error", message, None));
+        job.finish(JobState::Failed).await;
+        app_state.upgrading.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Archives the data directory's Config and Data folders
+pub async fn backup(app_state: web::Data<AppState>) -> impl Responder {
+    if app_state.backing_up.swap(true, Ordering::SeqCst) {
+        return HttpResponse::Conflict().json(ErrorResponse {
+            error: "A backup is already in progress".to_string(),
+        });
+    }
+
+    let backup_dir = app_state.backup_dir.clone();
+    let keep = app_state.backup_keep;
+    let result = tokio::task::spawn_blocking(move || {
+        backup::create_backup(Path::new(&*paths::DATA_DIR), &backup_dir, keep)
     })
+    .await;
+    app_state.backing_up.store(false, Ordering::SeqCst);
+
+    match result {
+        Ok(Ok(report)) => HttpResponse::Ok().json(report),
+        Ok(Err(e)) => {
+            error!("Backup failed: {:#}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: format!("Backup failed: {:#}", e),
+            })
+        }
+        Err(e) => {
+            error!("Backup task panicked: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Backup failed".to_string(),
+            })
+        }
+    }
+}
+
+/// Shown while Foundry is installed but not accepting connections, until readiness returns
+pub async fn maintenance_page(page_path: String) -> HttpResponse {
+    let mut response = HttpResponse::ServiceUnavailable();
+    response
+        .insert_header(("Retry-After", "5"))
+        .insert_header((header::CACHE_CONTROL, "no-store"));
+
+    match fs::read_to_string(&page_path).await {
+        Ok(page) => response.content_type("text/html; charset=utf-8").body(page),
+        Err(e) => {
+            warn!("Failed to read {}: {}", page_path, e);
+            response
+                .content_type("text/plain; charset=utf-8")
+                .body("Foundry VTT is restarting, please try again in a moment")
+        }
+    }
+}
+
+/// Answers paths the watcher does not serve itself: the maintenance page once
+/// Foundry is installed, so players asking for the game see why it is missing,
+/// and the installer before that or when the installation is broken
+pub async fn fallback_page(page_path: String, app_state: web::Data<AppState>) -> HttpResponse {
+    if !app_state.installer_active() {
+        maintenance_page(page_path).await
+    } else {
+        HttpResponse::Found()
+            .insert_header((header::LOCATION, "/"))
+            .finish()
+    }
 }
 
 // Helper functions to reduce code duplication
 
+/// Refuses installer requests once Foundry is installed. Behind the built-in proxy
+/// the installer stays reachable while Foundry is down, and must not overwrite a
+/// working installation. A broken one may be replaced.
+fn ensure_installer_active(app_state: &AppState) -> Result<(), HttpResponse> {
+    if !app_state.installer_active() {
+        warn!("Rejected installer request, FoundryVTT is already installed");
+        return Err(HttpResponse::Conflict().json(ErrorResponse {
+            error: "FoundryVTT is already installed, use the management API to upgrade it"
+                .to_string(),
+        }));
+    }
+    Ok(())
+}
+
 /// Ensures the target directory exists and returns its path
 async fn ensure_target_directory(
     event_tx: &broadcast::Sender<ProgressEvent>,
@@ -62,11 +355,41 @@ async fn ensure_target_directory(
     Ok(target_directory)
 }
 
+/// Removes a broken installation before its replacement is written, so files
+/// the new archive does not contain cannot break it again
+async fn clear_broken_installation(
+    app_state: &AppState,
+    target_directory: &str,
+    event_tx: &broadcast::Sender<ProgressEvent>,
+) -> Result<(), HttpResponse> {
+    if app_state.install_failure.lock().unwrap().is_none() {
+        return Ok(());
+    }
+
+    info!(
+        "Removing the broken FoundryVTT installation in {}",
+        target_directory
+    );
+    if let Err(e) = upgrade::clear_installation(Path::new(target_directory), None).await {
+        error!("Failed to remove the broken installation: {}", e);
+        let _ = event_tx.send(ProgressEvent::new(
+            "error",
+            &format!("Failed to remove the broken installation: {}", e),
+            None,
+        ));
+        return Err(HttpResponse::InternalServerError().json(ErrorResponse {
+            error: format!("Failed to remove the broken installation: {}", e),
+        }));
+    }
+    Ok(())
+}
+
 /// Extracts a ZIP archive and cleans up the source file
 async fn extract_and_cleanup(
     archive_path: String,
     target_directory: String,
     event_tx: broadcast::Sender<ProgressEvent>,
+    metrics: &Metrics,
 ) -> Result<(), HttpResponse> {
     // Send extraction started event
     let _ = event_tx.send(ProgressEvent::new(
@@ -80,6 +403,7 @@ async fn extract_and_cleanup(
         archive_path.clone(),
         target_directory.clone(),
         event_tx.clone(),
+        metrics,
     )
     .await
     {
@@ -111,12 +435,25 @@ async fn extract_and_cleanup(
     Ok(())
 }
 
-/// Completes the installation process by checking for Foundry and shutting down the installer
+/// Completes the installation process by checking for Foundry and handing over to it
 async fn complete_installation(
     event_tx: broadcast::Sender<ProgressEvent>,
     app_state: web::Data<AppState>,
     success_message: &str,
 ) -> HttpResponse {
+    // Keep the installer up with an explanation rather than launching a Foundry that cannot start
+    let target_directory = crate::config::get_target_directory();
+    if let Err(message) = install_check::validate_installation(&target_directory) {
+        error!("❌ Installed FoundryVTT failed validation: {}", message);
+        *app_state.install_failure.lock().unwrap() = Some(message.clone());
+        let _ = event_tx.send(ProgressEvent::new("error", &message, None));
+        return HttpResponse::UnprocessableEntity().json(ErrorResponse { error: message });
+    }
+    info!(
+        "Foundry main.js detected after extraction: {}",
+        paths::resolve_foundry_script_path(&target_directory).display()
+    );
+
     // Send completion event
     let _ = event_tx.send(ProgressEvent::new(
         "complete",
@@ -124,24 +461,21 @@ async fn complete_installation(
         Some(100.0),
     ));
 
-    // Check for foundry script existence
-    let foundry_script_path = Path::new("/foundryvtt/resources/app/main.js");
-    if foundry_script_path.exists() {
-        info!(
-            "Foundry main.js detected after extraction: {}",
-            foundry_script_path.display()
-        );
-    } else {
-        warn!(
-            "Foundry main.js not found at expected path after extraction: {}",
-            foundry_script_path.display()
-        );
-    }
-
-    // Signal the server to shut down
-    if let Some(tx) = app_state.shutdown_sender.lock().unwrap().take() {
+    // Let main launch Foundry, which also stops the installer without the built-in proxy.
+    // After a broken installation the supervisor is already waiting for the new one.
+    let replaced_broken = app_state.install_failure.lock().unwrap().take().is_some();
+    app_state.auth.revoke_setup_token();
+    let signalled = if let Some(tx) = app_state.shutdown_sender.lock().unwrap().take() {
         let _ = tx.send(());
-        info!("Sent shutdown signal to Actix server");
+        true
+    } else if replaced_broken {
+        app_state.reinstalled.notify_one();
+        true
+    } else {
+        false
+    };
+    if signalled {
+        info!("Signalled that FoundryVTT is installed");
         let _ = event_tx.send(ProgressEvent::new(
             "transition",
             "Transitioning to Foundry VTT...",
@@ -156,13 +490,108 @@ async fn complete_installation(
     })
 }
 
+/// Starts an installer job, refusing while Foundry is installed or another job runs
+fn start_install_job(
+    app_state: &web::Data<AppState>,
+    source: &str,
+) -> Result<JobHandle, HttpResponse> {
+    ensure_installer_active(app_state)?;
+    jobs::start(app_state, InstallKind::Install, source).ok_or_else(|| {
+        HttpResponse::Conflict().json(ErrorResponse {
+            error: "An installation is already in progress".to_string(),
+        })
+    })
+}
+
+/// How an installer request ended, judged by its response
+fn job_state(response: &HttpResponse) -> JobState {
+    match response.status() {
+        status if status.is_success() => JobState::Succeeded,
+        StatusCode::UNPROCESSABLE_ENTITY => JobState::Invalid,
+        _ => JobState::Failed,
+    }
+}
+
+/// Downloads and installs `url` in a task of its own, so the job carries on
+/// when the request that started it goes away
+fn spawn_download_job(
+    app_state: &web::Data<AppState>,
+    url: String,
+) -> Result<(u64, JoinHandle<()>), HttpResponse> {
+    let job = start_install_job(app_state, &url)?;
+    let id = job.id;
+    let app_state = app_state.clone();
+    let task = tokio::spawn(async move {
+        let state = job_state(&download_and_install(&url, app_state, job.events.clone()).await);
+        job.finish(state).await;
+    });
+    Ok((id, task))
+}
+
+/// Waits for the download job and answers with its outcome. Kept for clients of
+/// the installer from before install jobs; new ones use `/api/v1/installs`.
 pub async fn download_and_extract(
     url_payload: web::Json<UrlPayload>,
     app_state: web::Data<AppState>,
 ) -> impl Responder {
-    let url = url_payload.url.clone();
-    let event_tx = app_state.event_channel.clone();
+    let (id, task) = match spawn_download_job(&app_state, url_payload.url.clone()) {
+        Ok(job) => job,
+        Err(response) => return response,
+    };
+    let _ = task.await;
+
+    match app_state.installs.get(id) {
+        Some(job) if job.state == JobState::Succeeded => HttpResponse::Ok().json(SuccessResponse {
+            message: format!("Downloaded and extracted content from: {}", job.source),
+        }),
+        Some(job) if job.state == JobState::Invalid => {
+            HttpResponse::UnprocessableEntity().json(ErrorResponse {
+                error: job.error.unwrap_or_default(),
+            })
+        }
+        job => HttpResponse::InternalServerError().json(ErrorResponse {
+            error: job.and_then(|job| job.error).unwrap_or_default(),
+        }),
+    }
+}
+
+/// Starts downloading and installing FoundryVTT and answers right away with the
+/// job's ID. Progress events carry the same ID.
+pub async fn create_install(
+    url_payload: web::Json<UrlPayload>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    match spawn_download_job(&app_state, url_payload.url.clone()) {
+        Ok((id, _)) => HttpResponse::Accepted()
+            .insert_header((header::LOCATION, format!("/api/v1/installs/{}", id)))
+            .json(JobAccepted {
+                id,
+                message: "Installation started".to_string(),
+            }),
+        Err(response) => response,
+    }
+}
+
+/// Reports the phase, progress, timings and error of an install job
+pub async fn install_job(id: web::Path<u64>, app_state: web::Data<AppState>) -> impl Responder {
+    match app_state.installs.get(*id) {
+        Some(job) => HttpResponse::Ok().json(job),
+        None => HttpResponse::NotFound().json(ErrorResponse {
+            error: format!("No install job with ID {}", id),
+        }),
+    }
+}
 
+/// Lists the running install job and the most recent finished ones, newest first
+pub async fn install_jobs(app_state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(app_state.installs.list())
+}
+
+async fn download_and_install(
+    url: &str,
+    app_state: web::Data<AppState>,
+    event_tx: broadcast::Sender<ProgressEvent>,
+) -> HttpResponse {
     info!("Received request to download and extract from URL: {}", url);
 
     // Send initial progress event
@@ -177,6 +606,10 @@ pub async fn download_and_extract(
         Ok(dir) => dir,
         Err(response) => return response,
     };
+    if let Err(response) = clear_broken_installation(&app_state, &target_directory, &event_tx).await
+    {
+        return response;
+    }
 
     let archive_path = format!("{}/archive.zip", target_directory);
     debug!("Archive will be saved to: {}", archive_path);
@@ -189,8 +622,13 @@ pub async fn download_and_extract(
     ));
 
     // Download the archive
-    if let Err(e) =
-        DownloadService::download_file_from_url(&url, &archive_path, event_tx.clone()).await
+    if let Err(e) = DownloadService::download_file_from_url(
+        url,
+        &archive_path,
+        event_tx.clone(),
+        &app_state.metrics,
+    )
+    .await
     {
         error!("Download error: {}", e);
         let _ = event_tx.send(ProgressEvent::new(
@@ -239,8 +677,13 @@ pub async fn download_and_extract(
     }
 
     // Extract and cleanup
-    if let Err(response) =
-        extract_and_cleanup(archive_path, target_directory, event_tx.clone()).await
+    if let Err(response) = extract_and_cleanup(
+        archive_path,
+        target_directory,
+        event_tx.clone(),
+        &app_state.metrics,
+    )
+    .await
     {
         return response;
     }
@@ -260,12 +703,25 @@ pub async fn download_and_extract(
 }
 
 pub async fn upload_and_extract(
-    mut payload: Multipart,
+    payload: Multipart,
     app_state: web::Data<AppState>,
 ) -> impl Responder {
-    let event_tx = app_state.event_channel.clone();
+    let job = match start_install_job(&app_state, "upload") {
+        Ok(job) => job,
+        Err(response) => return response,
+    };
+    let response = upload_and_install(payload, app_state.clone(), job.events.clone()).await;
+    job.finish(job_state(&response)).await;
+    response
+}
 
+async fn upload_and_install(
+    mut payload: Multipart,
+    app_state: web::Data<AppState>,
+    event_tx: broadcast::Sender<ProgressEvent>,
+) -> HttpResponse {
     info!("Received file upload request");
+    let started = Instant::now();
 
     // Send initial progress event
     let _ = event_tx.send(ProgressEvent::new(
@@ -279,6 +735,10 @@ pub async fn upload_and_extract(
         Ok(dir) => dir,
         Err(response) => return response,
     };
+    if let Err(response) = clear_broken_installation(&app_state, &target_directory, &event_tx).await
+    {
+        return response;
+    }
 
     let archive_path = format!("{}/archive.zip", target_directory);
     debug!("Archive will be saved to: {}", archive_path);
@@ -386,6 +846,9 @@ pub async fn upload_and_extract(
         "Upload complete: {} bytes in field '{}'",
         total_bytes, field_name
     );
+    app_state
+        .metrics
+        .record_upload(total_bytes, started.elapsed());
     let _ = event_tx.send(ProgressEvent::new(
         "uploaded",
         &format!(
@@ -422,8 +885,13 @@ pub async fn upload_and_extract(
     }
 
     // Extract and cleanup
-    if let Err(response) =
-        extract_and_cleanup(archive_path, target_directory, event_tx.clone()).await
+    if let Err(response) = extract_and_cleanup(
+        archive_path,
+        target_directory,
+        event_tx.clone(),
+        &app_state.metrics,
+    )
+    .await
     {
         return response;
     }
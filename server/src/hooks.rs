@@ -0,0 +1,298 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Stdio;
+use tokio::process::Command;
+use tokio::time::{Duration, timeout};
+use tracing::{debug, info, warn};
+
+use crate::config;
+use crate::privileges::RunAs;
+use crate::provision;
+use crate::reaper;
+use crate::utils::paths;
+
+/// Point in the Foundry lifecycle at which hooks run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookEvent {
+    PreLaunch,
+    PostExit,
+}
+
+impl HookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookEvent::PreLaunch => "pre-launch",
+            HookEvent::PostExit => "post-exit",
+        }
+    }
+}
+
+/// Hooks configured for one event: executables in a directory and/or a shell command
+#[derive(Debug, Clone)]
+pub struct HookSet {
+    pub dir: PathBuf,
+    pub command: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HooksConfig {
+    pub pre_launch: HookSet,
+    pub post_exit: HookSet,
+    /// Each hook is killed after this long
+    pub timeout: Duration,
+    /// A failing pre-launch hook keeps Foundry from starting
+    pub block_on_failure: bool,
+}
+
+impl HooksConfig {
+    fn set(&self, event: HookEvent) -> &HookSet {
+        match event {
+            HookEvent::PreLaunch => &self.pre_launch,
+            HookEvent::PostExit => &self.post_exit,
+        }
+    }
+}
+
+/// How the Foundry process ended, passed to post-exit hooks
+#[derive(Debug, Clone)]
+pub struct HookExit {
+    /// Why the process ended: exited, oom, watchdog, restart, upgrade, window-closed or shutdown
+    pub reason: &'static str,
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+    pub oom_killed: bool,
+}
+
+/// Details about the event that hooks receive as environment variables
+#[derive(Debug, Clone, Default)]
+pub struct HookContext {
+    /// Number of times Foundry was started before
+    pub restart_count: u32,
+    pub version: Option<String>,
+    pub exit: Option<HookExit>,
+}
+
+impl HookContext {
+    fn env(&self, event: HookEvent) -> Vec<(&'static str, String)> {
+        let mut env = vec![
+            ("FOUNDRY_HOOK_EVENT", event.as_str().to_string()),
+            ("FOUNDRY_RESTART_COUNT", self.restart_count.to_string()),
+            ("FOUNDRY_APP_DIR", paths::APPLICATION_DIR.to_string()),
+            ("FOUNDRY_DATA_DIR", paths::DATA_DIR.to_string()),
+        ];
+        if let Some(version) = &self.version {
+            env.push(("FOUNDRY_VERSION", version.clone()));
+        }
+        if let Some(exit) = &self.exit {
+            env.push(("FOUNDRY_EXIT_REASON", exit.reason.to_string()));
+            env.push(("FOUNDRY_OOM_KILLED", exit.oom_killed.to_string()));
+            if let Some(code) = exit.code {
+                env.push(("FOUNDRY_EXIT_CODE", code.to_string()));
+            }
+            if let Some(signal) = exit.signal {
+                env.push(("FOUNDRY_EXIT_SIGNAL", signal.to_string()));
+            }
+        }
+        env
+    }
+}
+
+enum Hook {
+    Script(PathBuf),
+    Shell(String),
+}
+
+impl Hook {
+    fn name(&self) -> String {
+        match self {
+            Hook::Script(path) => path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string()),
+            Hook::Shell(_) => "command".to_string(),
+        }
+    }
+
+    fn command(&self) -> Command {
+        match self {
+            Hook::Script(path) => Command::new(path),
+            Hook::Shell(command) => {
+                let mut cmd = Command::new("sh");
+                cmd.arg("-c").arg(command);
+                cmd
+            }
+        }
+    }
+}
+
+/// Executables in `set.dir` in file name order, followed by `set.command`
+fn collect_hooks(set: &HookSet) -> Vec<Hook> {
+    let mut scripts: Vec<PathBuf> = fs::read_dir(&set.dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            let hidden = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if hidden || !path.is_file() {
+                return false;
+            }
+            let executable = config::is_executable(path);
+            if !executable {
+                debug!("Skipping non-executable hook {}", path.display());
+            }
+            executable
+        })
+        .collect();
+    scripts.sort();
+
+    let mut hooks: Vec<Hook> = scripts.into_iter().map(Hook::Script).collect();
+    if let Some(command) = &set.command {
+        hooks.push(Hook::Shell(command.clone()));
+    }
+    hooks
+}
+
+/// Runs every hook configured for `event` in order.
+/// Returns false when at least one hook failed or timed out.
+pub async fn run(
+    config: &HooksConfig,
+    event: HookEvent,
+    context: &HookContext,
+    run_as: Option<RunAs>,
+) -> bool {
+    let hooks = collect_hooks(config.set(event));
+    if hooks.is_empty() {
+        return true;
+    }
+
+    info!("🪝 Running {} {} hook(s)", hooks.len(), event.as_str());
+    let env = context.env(event);
+    let mut succeeded = true;
+    for hook in hooks {
+        succeeded &= run_hook(&hook, &env, config.timeout, run_as).await;
+    }
+    succeeded
+}
+
+async fn run_hook(
+    hook: &Hook,
+    env: &[(&'static str, String)],
+    limit: Duration,
+    run_as: Option<RunAs>,
+) -> bool {
+    let name = hook.name();
+    let mut cmd = hook.command();
+    cmd.envs(env.iter().map(|(key, value)| (*key, value)))
+        .current_dir(&*paths::DATA_DIR)
+        // Own process group so a timeout also kills whatever the hook started
+        .process_group(0)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    for name in provision::SECRET_VARS {
+        cmd.env_remove(name);
+    }
+    if let Some(run_as) = run_as {
+        if let Some(home) = run_as.home_dir() {
+            cmd.env("HOME", home);
+        }
+        // SAFETY: RunAs::apply only makes async-signal-safe syscalls
+        unsafe {
+            cmd.pre_exec(move || run_as.apply());
+        }
+    }
+
+    let child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("⚠️ Failed to start hook {}: {}", name, e);
+            return false;
+        }
+    };
+    let pid = child.id();
+    if let Some(pid) = pid {
+        reaper::manage_child(pid);
+    }
+
+    let result = timeout(limit, child.wait_with_output()).await;
+    if let Some(pid) = pid {
+        reaper::release_child(pid);
+    }
+
+    let output = match result {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            warn!("⚠️ Failed to wait for hook {}: {}", name, e);
+            return false;
+        }
+        Err(_) => {
+            if let Some(pid) = pid {
+                // SAFETY: kill(2) with a negative PID signals the hook's process group
+                unsafe { libc::kill(-(pid as i32), libc::SIGKILL) };
+            }
+            warn!("⚠️ Hook {} timed out after {}s", name, limit.as_secs());
+            return false;
+        }
+    };
+
+    for line in String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .chain(String::from_utf8_lossy(&output.stderr).lines())
+    {
+        info!("[hook {}] {}", name, line);
+    }
+
+    if output.status.success() {
+        debug!("Hook {} finished", name);
+        true
+    } else {
+        warn!("⚠️ Hook {} failed with {}", name, output.status);
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_post_exit_env() {
+        let context = HookContext {
+            restart_count: 3,
+            version: Some("13.345".to_string()),
+            exit: Some(HookExit {
+                reason: "oom",
+                code: None,
+                signal: Some(9),
+                oom_killed: true,
+            }),
+        };
+        let env = context.env(HookEvent::PostExit);
+        let get = |key: &str| {
+            env.iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| value.as_str())
+        };
+
+        assert_eq!(get("FOUNDRY_HOOK_EVENT"), Some("post-exit"));
+        assert_eq!(get("FOUNDRY_RESTART_COUNT"), Some("3"));
+        assert_eq!(get("FOUNDRY_VERSION"), Some("13.345"));
+        assert_eq!(get("FOUNDRY_EXIT_REASON"), Some("oom"));
+        assert_eq!(get("FOUNDRY_EXIT_SIGNAL"), Some("9"));
+        assert_eq!(get("FOUNDRY_OOM_KILLED"), Some("true"));
+        assert_eq!(get("FOUNDRY_EXIT_CODE"), None);
+    }
+
+    #[test]
+    fn test_pre_launch_env_has_no_exit() {
+        let env = HookContext::default().env(HookEvent::PreLaunch);
+        assert!(
+            env.iter()
+                .any(|(name, value)| *name == "FOUNDRY_HOOK_EVENT" && value == "pre-launch")
+        );
+        assert!(!env.iter().any(|(name, _)| name.starts_with("FOUNDRY_EXIT")));
+    }
+}
@@ -6,15 +6,27 @@ use std::path::Path;
 use std::process::Command;
 use tracing::{debug, error, info, warn};
 
-use crate::config::AppConfig;
+use crate::config::{self, AppConfig};
+use crate::forward_auth;
+use crate::options::{self, OptionsPrecedence};
+use crate::privileges;
+use crate::provision;
+use crate::schedule;
+use crate::tls::TlsSource;
 use crate::utils::{paths, run_command};
+use crate::watchdog::{LivenessProbe, WatchdogConfig};
 
-pub fn initialize(app_config: &AppConfig) -> Result<()> {
+pub fn initialize(app_config: &mut AppConfig) -> Result<()> {
     print_banner()?;
     print_system_info()?;
     check_required_env()?;
     validate_env()?;
+    validate_foundry_options(app_config)?;
     ensure_directories()?;
+    manage_options_json(app_config)?;
+    provision_secrets()?;
+    prepare_foundry_user(app_config);
+    validate_node_binary(app_config)?;
 
     info!("Configuration Summary:");
     info!("  - Application directory: {}", app_config.target_dir);
@@ -23,14 +35,52 @@ pub fn initialize(app_config: &AppConfig) -> Result<()> {
         "  - Host: {}",
         env::var("APPLICATION_HOST").unwrap_or_else(|_| "foundry.vtt".to_string())
     );
-    info!(
-        "  - SSL Proxy: {}",
-        env::var("SSL_PROXY").unwrap_or_else(|_| "false".to_string())
-    );
+    info!("  - SSL Proxy: {}", app_config.foundry_options.proxy_ssl);
+    match &app_config.tls {
+        Some(TlsSource::Files { cert, .. }) => info!("  - TLS certificate: {}", cert.display()),
+        Some(TlsSource::SelfSigned { hostname, .. }) => {
+            info!("  - TLS certificate: self-signed for {}", hostname)
+        }
+        None => {}
+    }
     info!(
         "  - Port: {}",
         env::var("APPLICATION_PORT").unwrap_or_else(|_| "4444".to_string())
     );
+    if app_config.builtin_proxy {
+        info!("  - FoundryVTT internal port: {}", app_config.foundry_port);
+    }
+    if let Some(prefix) = &app_config.foundry_options.route_prefix {
+        info!("  - Route prefix: /{}", prefix);
+    }
+    if let Some(world) = &app_config.foundry_options.world {
+        info!("  - World: {}", world);
+    }
+    if let Ok(expr) = env::var("RESTART_SCHEDULE") {
+        info!("  - Restart schedule: {}", expr);
+    }
+    if let Ok(expr) = env::var("PLAY_WINDOW_START") {
+        info!(
+            "  - Play window: {} for {}",
+            expr,
+            env::var("PLAY_WINDOW_DURATION").unwrap_or_else(|_| "6h".to_string())
+        );
+    }
+    if !app_config.node_flags.is_empty() {
+        info!("  - Node flags: {}", app_config.node_flags.join(" "));
+    }
+    let limits = &app_config.resource_limits;
+    if !limits.is_empty() {
+        info!(
+            "  - Resource limits: address space {}, open files {}, core size {}",
+            describe_limit(
+                limits.address_space.map(|bytes| bytes / (1024 * 1024)),
+                " MB"
+            ),
+            describe_limit(limits.open_files, ""),
+            describe_limit(limits.core_size.map(|bytes| bytes / (1024 * 1024)), " MB")
+        );
+    }
     info!(
         "  - Empty App Dir On Start: {}",
         env::var("EMPTY_APP_DIR_ON_START").unwrap_or_else(|_| "false".to_string())
@@ -40,6 +90,12 @@ pub fn initialize(app_config: &AppConfig) -> Result<()> {
     Ok(())
 }
 
+fn describe_limit(value: Option<u64>, unit: &str) -> String {
+    value
+        .map(|v| format!("{}{}", v, unit))
+        .unwrap_or_else(|| "unlimited".to_string())
+}
+
 fn print_banner() -> Result<()> {
     info!("──────────────────────────────────────────────────────────");
     info!(
@@ -144,6 +200,322 @@ fn validate_env() -> Result<()> {
         return Err(anyhow!("Invalid APPLICATION_PORT"));
     }
 
+    match env::var("MANAGEMENT_PORT").map(|port| port.trim().parse::<u16>()) {
+        Ok(Err(_)) => {
+            error!("MANAGEMENT_PORT must be a port number");
+            return Err(anyhow!("Invalid MANAGEMENT_PORT"));
+        }
+        Ok(Ok(port)) if app_port.parse::<u32>() == Ok(port as u32) => {
+            error!(
+                "MANAGEMENT_PORT must differ from APPLICATION_PORT: {}",
+                port
+            );
+            return Err(anyhow!("Invalid MANAGEMENT_PORT"));
+        }
+        _ => {}
+    }
+
+    if let Ok(port) = env::var("FOUNDRY_INTERNAL_PORT") {
+        match port.trim().parse::<u16>() {
+            Ok(port) if app_port.parse::<u32>() == Ok(port as u32) => {
+                error!(
+                    "FOUNDRY_INTERNAL_PORT must differ from APPLICATION_PORT: {}",
+                    port
+                );
+                return Err(anyhow!("Invalid FOUNDRY_INTERNAL_PORT"));
+            }
+            Ok(_) => {}
+            Err(_) => {
+                error!("FOUNDRY_INTERNAL_PORT must be a port number: {}", port);
+                return Err(anyhow!("Invalid FOUNDRY_INTERNAL_PORT"));
+            }
+        }
+    }
+
+    for var in ["RESTART_SCHEDULE", "PLAY_WINDOW_START"] {
+        if let Ok(expr) = env::var(var)
+            && let Err(e) = schedule::parse_cron(&expr)
+        {
+            error!("{} is not a valid cron expression ({}): {}", var, expr, e);
+            return Err(anyhow!("Invalid {}", var));
+        }
+    }
+
+    if let Ok(value) = env::var("PLAY_WINDOW_DURATION")
+        && let Err(e) = schedule::parse_duration(&value)
+    {
+        error!("PLAY_WINDOW_DURATION is invalid: {}", e);
+        return Err(anyhow!("Invalid PLAY_WINDOW_DURATION"));
+    }
+
+    if let Ok(port) = env::var("PROXY_PORT")
+        && port.trim().parse::<u16>().is_err()
+    {
+        error!("PROXY_PORT must be a port number: {}", port);
+        return Err(anyhow!("Invalid PROXY_PORT"));
+    }
+
+    match (env::var("TLS_CERT"), env::var("TLS_KEY")) {
+        (Ok(cert), Ok(key)) => {
+            for (var, path) in [("TLS_CERT", cert), ("TLS_KEY", key)] {
+                if !Path::new(path.trim()).is_file() {
+                    error!("{} does not point to a file: {}", var, path);
+                    return Err(anyhow!("Invalid {}", var));
+                }
+            }
+            if config::env_flag("TLS_SELF_SIGNED", false) {
+                error!("TLS_SELF_SIGNED cannot be combined with TLS_CERT/TLS_KEY");
+                return Err(anyhow!("Invalid TLS_SELF_SIGNED"));
+            }
+        }
+        (Ok(_), Err(_)) | (Err(_), Ok(_)) => {
+            error!("TLS_CERT and TLS_KEY must be set together");
+            return Err(anyhow!("Invalid TLS_CERT/TLS_KEY"));
+        }
+        (Err(_), Err(_)) => {}
+    }
+
+    if let Ok(value) = env::var("FORWARD_AUTH_TRUSTED_PROXIES")
+        && let Err(e) = forward_auth::parse_cidrs(&value)
+    {
+        error!(
+            "FORWARD_AUTH_TRUSTED_PROXIES must be a list of CIDRs: {}",
+            e
+        );
+        return Err(anyhow!("Invalid FORWARD_AUTH_TRUSTED_PROXIES"));
+    }
+
+    if let Ok(value) = env::var("OPTIONS_JSON_PRECEDENCE")
+        && let Err(e) = value.parse::<OptionsPrecedence>()
+    {
+        error!("OPTIONS_JSON_PRECEDENCE must be env, file or off: {}", e);
+        return Err(anyhow!("Invalid OPTIONS_JSON_PRECEDENCE"));
+    }
+
+    for var in ["COMPRESS_STATIC", "COMPRESS_SOCKET"] {
+        if let Ok(value) = env::var(var)
+            && value.trim().parse::<bool>().is_err()
+        {
+            error!("{} must be true or false: {}", var, value);
+            return Err(anyhow!("Invalid {}", var));
+        }
+    }
+
+    // Read with `config::env_flag`, where anything but true would silently mean false
+    for var in [
+        "TLS_SELF_SIGNED",
+        "BUILTIN_PROXY",
+        "SSL_PROXY",
+        "UPNP",
+        "FOUNDRY_USE_NPX",
+        "WATCHDOG_ENABLED",
+        "PRE_LAUNCH_HOOK_BLOCKING",
+    ] {
+        if let Ok(value) = env::var(var)
+            && value.trim().to_lowercase().parse::<bool>().is_err()
+        {
+            error!("{} must be true or false: {}", var, value);
+            return Err(anyhow!("Invalid {}", var));
+        }
+    }
+
+    if let Ok(value) = env::var("WATCHDOG_PROBE")
+        && let Err(e) = value.trim().parse::<LivenessProbe>()
+    {
+        error!("WATCHDOG_PROBE must be http or tcp: {}", e);
+        return Err(anyhow!("Invalid WATCHDOG_PROBE"));
+    }
+
+    for var in ["FOUNDRY_EXTRA_ARGS", "NODE_EXTRA_FLAGS"] {
+        if let Ok(value) = env::var(var)
+            && let Err(e) = shell_words::split(&value)
+        {
+            error!("{} could not be parsed ({}): {}", var, e, value);
+            return Err(anyhow!("Invalid {}", var));
+        }
+    }
+
+    for var in ["PUID", "PGID"] {
+        if let Ok(value) = env::var(var)
+            && value.trim().parse::<u32>().is_err()
+        {
+            error!("{} must be a numeric id: {}", var, value);
+            return Err(anyhow!("Invalid {}", var));
+        }
+    }
+
+    for var in [
+        "NODE_MAX_OLD_SPACE_SIZE",
+        "FOUNDRY_LIMIT_AS_MB",
+        "FOUNDRY_LIMIT_NOFILE",
+        "FOUNDRY_LIMIT_CORE_MB",
+        "BACKUP_KEEP",
+        "STARTUP_FAILURE_LIMIT",
+        "LOG_BUFFER_LINES",
+        "READINESS_INTERVAL_SECS",
+        "WATCHDOG_FAILURES",
+        "WATCHDOG_WINDOW_SECS",
+        "WATCHDOG_INTERVAL_SECS",
+        "WATCHDOG_STARTUP_GRACE_SECS",
+        "HOOK_TIMEOUT_SECS",
+    ] {
+        if let Ok(value) = env::var(var)
+            && value.trim().parse::<u64>().is_err()
+        {
+            error!("{} must be a number: {}", var, value);
+            return Err(anyhow!("Invalid {}", var));
+        }
+    }
+
+    let watchdog = WatchdogConfig::from_env();
+    if watchdog.enabled {
+        let errors = watchdog.validate();
+        for e in &errors {
+            error!("{}", e);
+        }
+        if !errors.is_empty() {
+            return Err(anyhow!("Invalid watchdog settings"));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_foundry_options(app_config: &AppConfig) -> Result<()> {
+    let port_var = if app_config.builtin_proxy {
+        "FOUNDRY_INTERNAL_PORT"
+    } else {
+        "APPLICATION_PORT"
+    };
+    let mut errors = app_config.foundry_options.validate(port_var);
+    if app_config.builtin_proxy && app_config.foundry_options.ssl_cert.is_some() {
+        errors.push(
+            "SSL_CERT makes Foundry serve HTTPS itself, which the built-in proxy cannot forward. \
+             Set BUILTIN_PROXY=false to use it"
+                .to_string(),
+        );
+    }
+    if app_config.builtin_proxy && app_config.foundry_port == app_config.management_port {
+        errors.push("FOUNDRY_INTERNAL_PORT must differ from MANAGEMENT_PORT".to_string());
+    }
+    for e in &errors {
+        error!("{}", e);
+    }
+    if !errors.is_empty() {
+        return Err(anyhow!("Invalid Foundry launch options"));
+    }
+    Ok(())
+}
+
+/// Brings Config/options.json in line with the launch options, or the other way
+/// around when the file takes precedence
+fn manage_options_json(app_config: &mut AppConfig) -> Result<()> {
+    let precedence = app_config.options_precedence;
+    if precedence == OptionsPrecedence::Off {
+        debug!("OPTIONS_JSON_PRECEDENCE is off, leaving options.json alone");
+        return Ok(());
+    }
+
+    let path = options::options_json_path(&paths::DATA_DIR);
+    let mut file = options::read_options_json(&path)?;
+    let drifts = options::reconcile(&mut file, &mut app_config.foundry_options, precedence);
+
+    for drift in &drifts {
+        match &drift.file {
+            None => info!(
+                "options.json has no {}, setting it to {}",
+                drift.key, drift.config
+            ),
+            Some(value) => warn!(
+                "⚠️ options.json {} is {} but the watcher has {}, using the {} value",
+                drift.key,
+                value,
+                drift.config,
+                if drift.file_wins { "file" } else { "watcher" }
+            ),
+        }
+    }
+    // Values taken over from the file must be valid launch options as well
+    if drifts.iter().any(|drift| drift.file_wins) {
+        validate_foundry_options(app_config)?;
+    }
+
+    if drifts.iter().any(|drift| !drift.file_wins) {
+        options::write_options_json(&path, &file)?;
+        info!("📝 Updated {}", path.display());
+    } else {
+        debug!("{} matches the watcher configuration", path.display());
+    }
+    Ok(())
+}
+
+/// Writes the admin key and license key from the environment or secret files into Config
+fn provision_secrets() -> Result<()> {
+    if let Some(admin_key) = provision::read_secret("ADMIN_KEY")? {
+        provision::provision_admin_key(&paths::DATA_DIR, &admin_key)?;
+    }
+    if let Some(license_key) = provision::read_secret("FOUNDRY_LICENSE_KEY")? {
+        provision::provision_license(&paths::DATA_DIR, &license_key)?;
+    }
+    Ok(())
+}
+
+/// Hands the data and application directories to the account Foundry runs as
+fn prepare_foundry_user(app_config: &AppConfig) {
+    match app_config.run_as {
+        Some(run_as) => {
+            info!(
+                "Running as root, FoundryVTT will run as {}:{}",
+                run_as.uid, run_as.gid
+            );
+            privileges::chown_dirs(&[&paths::APPLICATION_DIR, &paths::DATA_DIR], run_as);
+        }
+        None if privileges::is_root() => {
+            warn!("⚠️ PUID is 0, FoundryVTT will run as root");
+        }
+        None => {
+            if env::var("PUID").is_ok() || env::var("PGID").is_ok() {
+                warn!("⚠️ PUID/PGID are ignored because the watcher is not running as root");
+            }
+        }
+    }
+}
+
+fn validate_node_binary(app_config: &AppConfig) -> Result<()> {
+    if app_config.use_npx {
+        info!("FOUNDRY_USE_NPX is set, Foundry will be launched through npx");
+        if config::find_on_path("npx").is_none() {
+            error!("FOUNDRY_USE_NPX is set but npx was not found on PATH");
+            return Err(anyhow!("npx not found on PATH"));
+        }
+        return Ok(());
+    }
+
+    let node_binary = &app_config.node_binary;
+    if !config::is_executable(Path::new(node_binary)) {
+        error!("Node binary is missing or not executable: {}", node_binary);
+        error!("   Set NODE_BINARY to a node executable or add node to PATH");
+        return Err(anyhow!("Invalid node binary: {}", node_binary));
+    }
+
+    let output = Command::new(node_binary)
+        .arg("--version")
+        .output()
+        .with_context(|| format!("Failed to execute {} --version", node_binary))?;
+    if !output.status.success() {
+        error!(
+            "Node binary {} failed to report its version: {}",
+            node_binary,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        return Err(anyhow!("Node binary {} is not usable", node_binary));
+    }
+
+    info!(
+        "Using node binary: {} ({})",
+        node_binary,
+        String::from_utf8_lossy(&output.stdout).trim()
+    );
     Ok(())
 }
 
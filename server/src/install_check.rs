@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::Path;
+
+use crate::utils::paths;
+
+/// Checks that `app_dir` holds a Foundry build that can run in this container.
+/// Catches the mistakes the installer cannot see from the archive alone: another
+/// platform's build, and an extraction that stopped halfway.
+pub fn validate_installation(app_dir: &str) -> Result<(), String> {
+    if has_windows_build(Path::new(app_dir)) {
+        return Err(
+            "This is the Windows build of FoundryVTT, download the Node.js build instead"
+                .to_string(),
+        );
+    }
+
+    let script = paths::resolve_foundry_script_path(app_dir);
+    if !script.is_file() {
+        return Err(
+            "The archive does not contain FoundryVTT's main.js, download the Node.js build"
+                .to_string(),
+        );
+    }
+
+    let package = script.with_file_name("package.json");
+    let parsed = fs::read_to_string(&package)
+        .ok()
+        .and_then(|package| serde_json::from_str::<serde_json::Value>(&package).ok());
+    if parsed.is_none() {
+        return Err(format!(
+            "{} is missing or corrupt, the archive may be incomplete",
+            package.display()
+        ));
+    }
+
+    let node_modules = script.with_file_name("node_modules");
+    if !node_modules.is_dir() {
+        return Err(format!(
+            "{} is missing, the archive may be incomplete",
+            node_modules.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Explains why the installer is back after Foundry failed to start `failures` times,
+/// quoting the last lines it wrote since those usually name the problem
+pub fn startup_failure_message(failures: u32, last_output: &[String]) -> String {
+    let mut message = format!(
+        "FoundryVTT exited before it started listening {} times in a row. \
+         The installation may be broken, install FoundryVTT again to replace it.",
+        failures
+    );
+    if !last_output.is_empty() {
+        message.push_str(" Last output: ");
+        message.push_str(&last_output.join(" | "));
+    }
+    message
+}
+
+/// The Windows build ships an executable next to its resources
+fn has_windows_build(app_dir: &Path) -> bool {
+    fs::read_dir(app_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .any(|entry| {
+            Path::new(&entry.file_name())
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("exe"))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_installation() {
+        let root = std::env::temp_dir().join(format!("install-check-{}", std::process::id()));
+        let app_dir = root.to_string_lossy().to_string();
+        fs::create_dir_all(&root).unwrap();
+        assert!(
+            validate_installation(&app_dir)
+                .unwrap_err()
+                .contains("main.js")
+        );
+
+        fs::write(root.join("main.js"), "").unwrap();
+        assert!(
+            validate_installation(&app_dir)
+                .unwrap_err()
+                .contains("package.json")
+        );
+
+        fs::write(root.join("package.json"), r#"{"version": "13.345"}"#).unwrap();
+        assert!(
+            validate_installation(&app_dir)
+                .unwrap_err()
+                .contains("node_modules")
+        );
+
+        fs::create_dir(root.join("node_modules")).unwrap();
+        assert_eq!(validate_installation(&app_dir), Ok(()));
+
+        fs::write(root.join("FoundryVTT.exe"), "").unwrap();
+        assert!(
+            validate_installation(&app_dir)
+                .unwrap_err()
+                .contains("Windows")
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
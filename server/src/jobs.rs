@@ -0,0 +1,338 @@
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use crate::events::ProgressEvent;
+use crate::server::AppState;
+
+/// Finished jobs kept for `/api/v1/installs`
+const KEPT_JOBS: usize = 20;
+
+/// Event types sent while an archive is fetched and installed
+const INSTALL_PHASES: [&str; 12] = [
+    "start",
+    "downloading",
+    "downloaded",
+    "uploading",
+    "uploaded",
+    "extracting",
+    "extracted",
+    "cleanup",
+    "complete",
+    "transition",
+    "upgrade",
+    "error",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallKind {
+    /// Through the installer, before Foundry is installed or to replace a broken copy
+    Install,
+    /// Through the management API, replacing a working installation
+    Upgrade,
+}
+
+impl InstallKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InstallKind::Install => "install",
+            InstallKind::Upgrade => "upgrade",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Running,
+    Succeeded,
+    /// The archive did not contain a working FoundryVTT
+    Invalid,
+    Failed,
+}
+
+impl JobState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobState::Running => "running",
+            JobState::Succeeded => "succeeded",
+            JobState::Invalid => "invalid",
+            JobState::Failed => "failed",
+        }
+    }
+}
+
+/// When a job entered one of its phases
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseTiming {
+    pub phase: String,
+    pub started_at: DateTime<Utc>,
+}
+
+/// An installation or upgrade, running or finished
+#[derive(Debug, Clone, Serialize)]
+pub struct InstallJob {
+    pub id: u64,
+    pub kind: InstallKind,
+    /// URL the archive is downloaded from, or `upload`
+    pub source: String,
+    pub state: JobState,
+    /// Type of the last event the job sent, e.g. `downloading`
+    pub phase: String,
+    pub message: String,
+    pub progress: Option<f32>,
+    /// Why a job that did not succeed ended
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub duration_secs: Option<f64>,
+    pub phases: Vec<PhaseTiming>,
+}
+
+impl InstallJob {
+    fn new(id: u64, kind: InstallKind, source: &str) -> Self {
+        let started_at = Utc::now();
+        Self {
+            id,
+            kind,
+            source: source.to_string(),
+            state: JobState::Running,
+            phase: "start".to_string(),
+            message: String::new(),
+            progress: Some(0.0),
+            error: None,
+            started_at,
+            finished_at: None,
+            duration_secs: None,
+            phases: vec![PhaseTiming {
+                phase: "start".to_string(),
+                started_at,
+            }],
+        }
+    }
+
+    /// Takes over the phase and message of an event. Events without progress,
+    /// such as errors, keep the last known value.
+    fn apply(&mut self, event: &ProgressEvent) {
+        if self.state != JobState::Running || !INSTALL_PHASES.contains(&event.event_type.as_str()) {
+            return;
+        }
+        if self.phase != event.event_type {
+            self.phase = event.event_type.clone();
+            self.phases.push(PhaseTiming {
+                phase: event.event_type.clone(),
+                started_at: Utc::now(),
+            });
+        }
+        self.message = event.message.clone();
+        if event.progress.is_some() {
+            self.progress = event.progress;
+        }
+        if event.event_type == "error" {
+            self.error = Some(event.message.clone());
+        }
+    }
+
+    fn finish(&mut self, state: JobState, fallback_error: &str) {
+        let finished_at = Utc::now();
+        self.state = state;
+        self.finished_at = Some(finished_at);
+        self.duration_secs =
+            Some((finished_at - self.started_at).num_milliseconds() as f64 / 1000.0);
+        if state != JobState::Succeeded && self.error.is_none() {
+            self.error = Some(fallback_error.to_string());
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    last_id: u64,
+    /// Oldest first, so a running job is always the last one
+    jobs: VecDeque<InstallJob>,
+}
+
+/// The running install job and the most recent finished ones
+#[derive(Debug, Default)]
+pub struct InstallJobs {
+    inner: Mutex<Registry>,
+}
+
+impl InstallJobs {
+    /// Registers a running job and returns its ID, or None while another one runs
+    fn begin(&self, kind: InstallKind, source: &str) -> Option<u64> {
+        let mut registry = self.inner.lock().unwrap();
+        if registry
+            .jobs
+            .back()
+            .is_some_and(|job| job.state == JobState::Running)
+        {
+            return None;
+        }
+        registry.last_id += 1;
+        let id = registry.last_id;
+        registry.jobs.push_back(InstallJob::new(id, kind, source));
+        while registry.jobs.len() > KEPT_JOBS {
+            registry.jobs.pop_front();
+        }
+        Some(id)
+    }
+
+    fn update(&self, id: u64, update: impl FnOnce(&mut InstallJob)) {
+        let mut registry = self.inner.lock().unwrap();
+        if let Some(job) = registry.jobs.iter_mut().find(|job| job.id == id) {
+            update(job);
+        }
+    }
+
+    pub fn get(&self, id: u64) -> Option<InstallJob> {
+        let registry = self.inner.lock().unwrap();
+        registry.jobs.iter().find(|job| job.id == id).cloned()
+    }
+
+    /// The job in progress, if any
+    pub fn current(&self) -> Option<InstallJob> {
+        let registry = self.inner.lock().unwrap();
+        registry
+            .jobs
+            .back()
+            .filter(|job| job.state == JobState::Running)
+            .cloned()
+    }
+
+    /// Every kept job, newest first
+    pub fn list(&self) -> Vec<InstallJob> {
+        let registry = self.inner.lock().unwrap();
+        registry.jobs.iter().rev().cloned().collect()
+    }
+}
+
+/// Held by whatever runs a job. Events sent on `events` update the job and are
+/// passed on to the shared event channel tagged with its ID. Dropping the handle
+/// without calling `finish`, e.g. when the request was cancelled, fails the job.
+pub struct JobHandle {
+    pub id: u64,
+    pub kind: InstallKind,
+    pub events: broadcast::Sender<ProgressEvent>,
+    forwarder: Option<JoinHandle<()>>,
+    app_state: web::Data<AppState>,
+}
+
+impl fmt::Debug for JobHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JobHandle")
+            .field("id", &self.id)
+            .field("kind", &self.kind)
+            .finish()
+    }
+}
+
+/// Starts a job, or returns None while another one is running
+pub fn start(
+    app_state: &web::Data<AppState>,
+    kind: InstallKind,
+    source: &str,
+) -> Option<JobHandle> {
+    let id = app_state.installs.begin(kind, source)?;
+    let (events, mut rx) = broadcast::channel::<ProgressEvent>(100);
+
+    let forward_state = app_state.clone();
+    let forwarder = tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(mut event) => {
+                    forward_state.installs.update(id, |job| job.apply(&event));
+                    event.job_id = Some(id);
+                    let _ = forward_state.event_channel.send(event);
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    });
+
+    Some(JobHandle {
+        id,
+        kind,
+        events,
+        forwarder: Some(forwarder),
+        app_state: app_state.clone(),
+    })
+}
+
+impl JobHandle {
+    /// Records how the job ended, once every event it sent has been applied.
+    /// Clones of `events` must be dropped by then.
+    pub async fn finish(mut self, state: JobState) {
+        // Replacing the sender closes the job's channel, which stops the forwarder
+        self.events = broadcast::channel(1).0;
+        if let Some(forwarder) = self.forwarder.take() {
+            let _ = forwarder.await;
+        }
+        self.end(state, "The installation failed");
+    }
+
+    fn end(&self, state: JobState, fallback_error: &str) {
+        self.app_state
+            .installs
+            .update(self.id, |job| job.finish(state, fallback_error));
+        self.app_state
+            .metrics
+            .record_install(self.kind.as_str(), state.as_str());
+    }
+}
+
+impl Drop for JobHandle {
+    fn drop(&mut self) {
+        if self.forwarder.is_some() {
+            self.end(JobState::Failed, "The installation was cancelled");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_follows_install_events() {
+        let jobs = InstallJobs::default();
+        let id = jobs.begin(InstallKind::Install, "upload").unwrap();
+        assert_eq!(
+            jobs.begin(InstallKind::Upgrade, "https://example.com"),
+            None
+        );
+
+        jobs.update(id, |job| {
+            job.apply(&ProgressEvent::new("uploading", "Uploading", Some(30.0)));
+            job.apply(&ProgressEvent::new(
+                "restart",
+                "Foundry restarting",
+                Some(90.0),
+            ));
+        });
+        let job = jobs.current().unwrap();
+        assert_eq!(job.phase, "uploading");
+        assert_eq!(job.progress, Some(30.0));
+        assert_eq!(job.phases.len(), 2);
+
+        jobs.update(id, |job| {
+            job.apply(&ProgressEvent::new("error", "Extraction failed", None));
+            job.finish(JobState::Failed, "The installation failed");
+            job.apply(&ProgressEvent::new("extracting", "Late event", Some(60.0)));
+        });
+        assert!(jobs.current().is_none());
+        let job = jobs.get(id).unwrap();
+        assert_eq!(job.state, JobState::Failed);
+        assert_eq!(job.phase, "error");
+        assert_eq!(job.error.as_deref(), Some("Extraction failed"));
+
+        assert_eq!(jobs.begin(InstallKind::Install, "upload"), Some(id + 1));
+    }
+}
@@ -1,33 +1,135 @@
 use crate::config::AppConfig;
-use std::path::Path;
-use std::process::Stdio;
-use tokio::process::Command;
-use tokio::sync::oneshot;
-use tokio::time::{Duration, sleep};
+use crate::events::ProgressEvent;
+use crate::hooks::{self, HookContext, HookEvent, HookExit};
+use crate::install_check;
+use crate::jobs::{JobHandle, JobState};
+use crate::limits;
+use crate::logs::{self, LogStream};
+use crate::ports;
+use crate::privileges;
+use crate::provision;
+use crate::readiness::{self, ReadinessState};
+use crate::reaper;
+use crate::schedule::{self, ScheduledStop};
+use crate::server::{self, AppState};
+use crate::upgrade;
+use crate::utils::paths;
+use crate::watchdog::{self, WatchdogReport};
+use crate::worlds;
+use actix_web::web;
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use tokio::process::{Child, Command};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{Duration, sleep, timeout};
 use tracing::{debug, error, info, warn};
 
+/// How long a terminated Foundry gets to exit before it is killed
+const TERMINATE_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// How long to wait before retrying after a blocking pre-launch hook failed
+const HOOK_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// How long to wait before checking again for a missing FOUNDRY_WORLD
+const WORLD_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// How long the installer or the scheduled-off page get to release Foundry's port
+const PORT_RELEASE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How long to wait before trying again when something else kept Foundry's port
+const PORT_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Snapshot of the Foundry child process, shared with the HTTP server
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SupervisorStatus {
+    /// Exact command line of the current or last spawned process
+    pub command_line: Vec<String>,
+    pub pid: Option<u32>,
+    /// When the current or last process was spawned
+    pub started_at: Option<DateTime<Utc>>,
+    /// Number of times Foundry was started before the current process
+    pub restart_count: u32,
+    /// Diagnostics from the last time the watchdog restarted Foundry
+    pub last_watchdog: Option<WatchdogReport>,
+    /// Set while Foundry is kept stopped outside its play window
+    pub scheduled_off_until: Option<DateTime<Utc>>,
+    pub next_restart: Option<DateTime<Utc>>,
+    /// How the last Foundry process ended on its own
+    pub last_exit: Option<ExitReport>,
+}
+
+/// Outcome of a Foundry process that exited without being stopped by the supervisor
+#[derive(Debug, Clone, Serialize)]
+pub struct ExitReport {
+    pub at: DateTime<Utc>,
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+    /// The kernel OOM killer ended the process
+    pub oom_killed: bool,
+}
+
+pub type SharedSupervisorStatus = Arc<Mutex<SupervisorStatus>>;
+
+/// Requests sent to the supervisor through the management API
+#[derive(Debug)]
+pub enum SupervisorCommand {
+    /// Restart the running Foundry process
+    Restart,
+    /// Stop Foundry, install the downloaded release archive and start it again
+    Upgrade { archive: PathBuf, job: JobHandle },
+}
+
 pub async fn launch_foundry_process(
     shutdown_rx: Option<oneshot::Receiver<()>>,
     config: &AppConfig,
+    app_state: web::Data<AppState>,
 ) {
-    // Convert string args to &str for the launch_foundry function
-    let args: Vec<&str> = config.foundry_args.iter().map(|s| s.as_str()).collect();
-
     // Launch Foundry in the same task, passing the shutdown channel
-    launch_foundry(&args, &config.foundry_script, shutdown_rx).await;
+    launch_foundry(config, shutdown_rx, app_state).await;
 }
 
+/// Runs Foundry as described by `config` and restarts it whenever it exits
 pub async fn launch_foundry(
-    args: &[&str],
-    script_path: &str,
+    config: &AppConfig,
     shutdown_rx: Option<oneshot::Receiver<()>>,
+    app_state: web::Data<AppState>,
 ) {
-    let script_path_owned = script_path.to_string();
+    let status = &app_state.supervisor;
+    let command = config.foundry_command();
+    let script_path_owned = config.foundry_script.clone();
+    let probe_interval = Duration::from_secs(config.readiness_interval_secs);
+    let status_url = config.foundry_options.status_url();
 
-    // Take ownership of the shutdown_rx outside the loop
-    let mut shutdown_rx_option = shutdown_rx;
+    // Keep the shutdown channel across restarts
+    let mut shutdown_rx = shutdown_rx;
+    let mut commands = app_state.command_receiver.lock().unwrap().take();
+    let mut restart_count: u32 = 0;
+    // Runs in a row that ended before Foundry accepted connections
+    let mut startup_failures: u32 = 0;
+    let mut pending_upgrade: Option<(PathBuf, JobHandle)> = None;
 
     loop {
+        // Updates are installed while Foundry is stopped, including ones requested meanwhile
+        if let Some((archive, job)) = pending_upgrade
+            .take()
+            .or_else(|| queued_upgrade(&mut commands))
+        {
+            install_update(config, &app_state, &archive, job).await;
+        }
+
+        // Keep Foundry stopped outside its play window
+        if let Some(back_at) = config.schedules.scheduled_off(Local::now()) {
+            if !wait_for_play_window(config, &app_state, back_at, &mut shutdown_rx).await {
+                return;
+            }
+            continue;
+        }
+
         // Wait until the script file is present
         if !Path::new(&script_path_owned).exists() {
             warn!("⚠️ Script not found at {}, waiting...", script_path_owned);
@@ -35,22 +137,94 @@ pub async fn launch_foundry(
             continue;
         }
 
+        let mut hook_context = HookContext {
+            restart_count,
+            version: paths::foundry_version(&config.target_dir),
+            exit: None,
+        };
+        if !hooks::run(
+            &config.hooks,
+            HookEvent::PreLaunch,
+            &hook_context,
+            config.run_as,
+        )
+        .await
+            && config.hooks.block_on_failure
+        {
+            error!("❌ A pre-launch hook failed, not starting FoundryVTT");
+            let _ = app_state.event_channel.send(ProgressEvent::new(
+                "hook",
+                "A pre-launch hook failed, FoundryVTT was not started",
+                None,
+            ));
+            sleep(HOOK_RETRY_DELAY).await;
+            continue;
+        }
+
+        // Checked after the hooks, which may have restored the world
+        if let Some(world) = &config.foundry_options.world
+            && !worlds::world_exists(&paths::DATA_DIR, world)
+        {
+            let message =
+                worlds::missing_world_message(world, &worlds::list_worlds(&paths::DATA_DIR));
+            error!("❌ Not starting FoundryVTT: {}", message);
+            let _ = app_state
+                .event_channel
+                .send(ProgressEvent::new("world", &message, None));
+            sleep(WORLD_RETRY_DELAY).await;
+            continue;
+        }
+
+        // Foundry would only fail with EADDRINUSE, which says nothing about who holds the port
+        if let Err(conflict) =
+            ports::wait_until_free(config.foundry_port, PORT_RELEASE_TIMEOUT).await
+        {
+            let message = format!("Not starting FoundryVTT, {}", conflict);
+            error!("❌ {}", message);
+            let _ = app_state
+                .event_channel
+                .send(ProgressEvent::new("port", &message, None));
+            sleep(PORT_RETRY_DELAY).await;
+            continue;
+        }
+
         info!("🚀 Launching FoundryVTT with script: {}", script_path_owned);
-        debug!(
-            "Launch command: npx --yes node {} with args: {:?}",
-            script_path_owned, args
-        );
+        debug!("Launch command: {:?}", command);
 
-        let mut cmd = Command::new("npx");
-        cmd.arg("--yes")
-            .arg("node")
-            .arg(&script_path_owned)
-            .args(args)
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit());
+        let mut cmd = Command::new(&command[0]);
+        cmd.args(&command[1..])
+            // Own process group so signals reach node and everything it spawns
+            .process_group(0)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        for name in provision::SECRET_VARS {
+            cmd.env_remove(name);
+        }
+        if let Some(run_as) = config.run_as
+            && let Some(home) = run_as.home_dir()
+        {
+            cmd.env("HOME", home);
+        }
+        if !config.resource_limits.is_empty() || config.run_as.is_some() {
+            let resource_limits = config.resource_limits.clone();
+            let run_as = config.run_as;
+            // SAFETY: the closure only makes async-signal-safe syscalls. Limits are
+            // applied first, while the child may still raise them as root.
+            unsafe {
+                cmd.pre_exec(move || {
+                    resource_limits.apply()?;
+                    match run_as {
+                        Some(run_as) => run_as.apply(),
+                        None => Ok(()),
+                    }
+                });
+            }
+        }
 
         debug!("Full command: {:?}", cmd);
 
+        let oom_kills_before = limits::oom_kill_count();
+
         let mut child = match cmd.spawn() {
             Ok(child) => child,
             Err(e) => {
@@ -60,51 +234,497 @@ pub async fn launch_foundry(
             }
         };
 
+        // Capture output into the log buffer while still echoing it
+        let mut output_tasks = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            output_tasks.push(logs::capture(
+                stdout,
+                LogStream::Stdout,
+                app_state.logs.clone(),
+            ));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            output_tasks.push(logs::capture(
+                stderr,
+                LogStream::Stderr,
+                app_state.logs.clone(),
+            ));
+        }
+
+        let child_id = child.id();
+        if let Some(pid) = child_id {
+            reaper::register_foundry(pid);
+        }
+        let probe = readiness::spawn_probe(
+            config.foundry_port,
+            status_url.clone(),
+            probe_interval,
+            app_state.clone(),
+        );
+        let next_stop = config.schedules.next_stop(Local::now());
+        {
+            let mut status = status.lock().unwrap();
+            status.command_line = command.clone();
+            status.pid = child_id;
+            status.started_at = Some(Utc::now());
+            status.restart_count = restart_count;
+            status.next_restart = config
+                .schedules
+                .next_restart(Local::now())
+                .map(schedule::to_utc);
+        }
         info!("FoundryVTT process started");
 
-        // Handle shutdown signal if provided
-        if let Some(shutdown_rx) = shutdown_rx_option.take() {
-            let child_id = child.id();
-            tokio::select! {
-                exit_status = child.wait() => {
-                    match exit_status {
-                        Ok(exit) => {
+        let shutdown = async {
+            match shutdown_rx.as_mut() {
+                Some(rx) => {
+                    let _ = rx.await;
+                }
+                None => std::future::pending::<()>().await,
+            }
+        };
+        let scheduled_stop = async {
+            match next_stop {
+                Some((at, kind)) => {
+                    sleep_until_local(at).await;
+                    kind
+                }
+                None => std::future::pending().await,
+            }
+        };
+        let hang = watchdog::watch(
+            &config.watchdog,
+            config.foundry_port,
+            &status_url,
+            child_id,
+            app_state.clone(),
+        );
+
+        let supervisor_command = next_command(&mut commands);
+
+        let (reason, exit) = tokio::select! {
+            exit_status = child.wait() => {
+                match exit_status {
+                    Ok(exit) => {
+                        let report = exit_report(exit, oom_kills_before);
+                        let reason = if report.oom_killed { "oom" } else { "exited" };
+                        if report.oom_killed {
+                            error!(
+                                "💥 FoundryVTT was killed by the OOM killer, consider raising the container memory or NODE_MAX_OLD_SPACE_SIZE"
+                            );
+                            let _ = app_state.event_channel.send(ProgressEvent::new(
+                                "oom",
+                                "FoundryVTT ran out of memory and is being restarted",
+                                None,
+                            ));
+                        } else {
                             warn!("⚠️ FoundryVTT exited with: {}", exit);
                         }
-                        Err(e) => {
-                            error!("❌ Failed to wait for FoundryVTT: {}", e);
-                        }
+                        status.lock().unwrap().last_exit = Some(report);
+                        (reason, Some(exit))
                     }
-                },
-                _ = shutdown_rx => {
-                    info!("Received shutdown signal, terminating FoundryVTT process");
-                    if let Some(pid) = child_id {
-                        info!("Sending SIGTERM to FoundryVTT process (PID: {})", pid);
-                        if let Err(e) = child.kill().await {
-                            error!("Failed to kill FoundryVTT process: {}", e);
-                        }
+                    Err(e) => {
+                        error!("❌ Failed to wait for FoundryVTT: {}", e);
+                        ("exited", None)
+                    }
+                }
+            },
+            kind = scheduled_stop => {
+                let (reason, message) = match kind {
+                    ScheduledStop::Restart => ("restart", "Scheduled maintenance restart of FoundryVTT"),
+                    ScheduledStop::WindowClosed => ("window-closed", "Play window closed, stopping FoundryVTT"),
+                };
+                info!("🕔 {}", message);
+                let _ = app_state
+                    .event_channel
+                    .send(ProgressEvent::new("schedule", message, None));
+                (reason, terminate(&mut child).await)
+            },
+            Some(received) = supervisor_command => {
+                match received {
+                    SupervisorCommand::Restart => {
+                        info!("🔄 Restarting FoundryVTT as requested through the management API");
+                        let _ = app_state.event_channel.send(ProgressEvent::new(
+                            "restart",
+                            "FoundryVTT is restarting",
+                            None,
+                        ));
+                        ("restart", terminate(&mut child).await)
                     }
-                    // Wait for child process to exit after kill signal
-                    if let Err(e) = child.wait().await {
-                        error!("Error waiting for FoundryVTT to exit: {}", e);
+                    SupervisorCommand::Upgrade { archive, job } => {
+                        info!("⬆️ Stopping FoundryVTT to install an update");
+                        pending_upgrade = Some((archive, job));
+                        ("upgrade", terminate(&mut child).await)
                     }
-                    info!("FoundryVTT process terminated");
-                    return; // Exit the function, don't restart
                 }
-            }
-        } else {
-            // Without shutdown channel, just wait for the process
-            match child.wait().await {
-                Ok(exit) => {
-                    warn!("⚠️ FoundryVTT exited with: {}", exit);
+            },
+            report = hang => {
+                warn!("Watchdog is terminating the unresponsive FoundryVTT process");
+                let _ = app_state.event_channel.send(ProgressEvent::new(
+                    "watchdog",
+                    "FoundryVTT stopped responding and is being restarted",
+                    None,
+                ));
+                status.lock().unwrap().last_watchdog = Some(report);
+                ("watchdog", terminate(&mut child).await)
+            },
+            _ = shutdown => {
+                info!("Received shutdown signal, terminating FoundryVTT process");
+                let exit = terminate(&mut child).await;
+                if let Some(exit) = exit {
+                    app_state.metrics.record_exit("shutdown", exit.code(), exit.signal());
                 }
-                Err(e) => {
-                    error!("❌ Failed to wait for FoundryVTT: {}", e);
+                if let Some(pid) = child_id {
+                    reaper::unregister_foundry(pid);
                 }
+                probe.abort();
+                app_state.readiness.update(ReadinessState::Stopped, None, &app_state.event_channel);
+                info!("FoundryVTT process terminated");
+                hook_context.exit = Some(hook_exit("shutdown", exit));
+                hooks::run(&config.hooks, HookEvent::PostExit, &hook_context, config.run_as).await;
+                return; // Exit the function, don't restart
             }
+        };
+
+        if let Some(pid) = child_id {
+            reaper::unregister_foundry(pid);
+        }
+        if let Some(exit) = exit {
+            app_state
+                .metrics
+                .record_exit(reason, exit.code(), exit.signal());
+        }
+        status.lock().unwrap().pid = None;
+        probe.abort();
+        let listened = !matches!(
+            app_state.readiness.state(),
+            ReadinessState::Stopped | ReadinessState::Starting
+        );
+        if reason == "exited" && !listened {
+            startup_failures += 1;
+        } else {
+            startup_failures = 0;
+        }
+        app_state
+            .readiness
+            .update(ReadinessState::Stopped, None, &app_state.event_channel);
+
+        // Let the capture tasks drain whatever the process wrote last. Orphaned
+        // grandchildren may still hold the pipes open, so don't wait forever.
+        for task in output_tasks {
+            let _ = timeout(Duration::from_secs(2), task).await;
+        }
+
+        hook_context.exit = Some(hook_exit(reason, exit));
+        hooks::run(
+            &config.hooks,
+            HookEvent::PostExit,
+            &hook_context,
+            config.run_as,
+        )
+        .await;
+        restart_count += 1;
+        app_state.metrics.record_restart();
+
+        // Restarting a Foundry that never comes up only hides the installer from the operator
+        if config.startup_failure_limit > 0 && startup_failures >= config.startup_failure_limit {
+            let last_output: Vec<String> = app_state
+                .logs
+                .tail(3)
+                .into_iter()
+                .map(|line| line.line)
+                .collect();
+            let message = install_check::startup_failure_message(startup_failures, &last_output);
+            if !wait_for_reinstall(config, &app_state, message, &mut shutdown_rx, &mut commands)
+                .await
+            {
+                return;
+            }
+            startup_failures = 0;
+            continue;
         }
 
         // Retry after 5 seconds if the script or process exits (only if we didn't get a shutdown signal)
         sleep(Duration::from_secs(5)).await;
     }
 }
+
+/// Waits for the next request from the management API
+async fn next_command(
+    commands: &mut Option<mpsc::Receiver<SupervisorCommand>>,
+) -> Option<SupervisorCommand> {
+    match commands {
+        Some(commands) => commands.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Drains requests that arrived while Foundry was not running, returning the
+/// archive of a requested update. There is nothing to restart at that point.
+fn queued_upgrade(
+    commands: &mut Option<mpsc::Receiver<SupervisorCommand>>,
+) -> Option<(PathBuf, JobHandle)> {
+    let commands = commands.as_mut()?;
+    let mut archive = None;
+    while let Ok(command) = commands.try_recv() {
+        match command {
+            SupervisorCommand::Restart => {
+                debug!("Ignoring restart request, FoundryVTT is not running")
+            }
+            SupervisorCommand::Upgrade { archive: path, job } => archive = Some((path, job)),
+        }
+    }
+    archive
+}
+
+/// Installs a downloaded Foundry release while Foundry is stopped, returning whether it succeeded
+async fn install_update(
+    config: &AppConfig,
+    app_state: &web::Data<AppState>,
+    archive: &Path,
+    job: JobHandle,
+) -> bool {
+    let _ = job.events.send(ProgressEvent::new(
+        "upgrade",
+        "Installing FoundryVTT update",
+        None,
+    ));
+
+    let installed = upgrade::install_update(
+        &config.target_dir,
+        archive,
+        job.events.clone(),
+        &app_state.metrics,
+    )
+    .await;
+    match &installed {
+        Ok(()) => {
+            if let Some(run_as) = config.run_as {
+                privileges::chown_dirs(&[&config.target_dir], run_as);
+            }
+            info!(
+                "✅ FoundryVTT {} installed",
+                paths::foundry_version(&config.target_dir).unwrap_or_default()
+            );
+            let _ = job.events.send(ProgressEvent::new(
+                "upgrade",
+                "FoundryVTT update installed",
+                Some(100.0),
+            ));
+        }
+        Err(e) => {
+            error!(
+                "❌ Failed to install the FoundryVTT update, keeping the current version: {}",
+                e
+            );
+            let _ = job.events.send(ProgressEvent::new(
+                "error",
+                &format!("Failed to install the FoundryVTT update: {}", e),
+                None,
+            ));
+        }
+    }
+    job.finish(if installed.is_ok() {
+        JobState::Succeeded
+    } else {
+        JobState::Failed
+    })
+    .await;
+    app_state.upgrading.store(false, Ordering::SeqCst);
+    installed.is_ok()
+}
+
+/// Brings the installer back, with `message` as the explanation, until a working
+/// Foundry replaces the broken one through the installer or the management API.
+/// Returns false when a shutdown was requested in the meantime.
+async fn wait_for_reinstall(
+    config: &AppConfig,
+    app_state: &web::Data<AppState>,
+    message: String,
+    shutdown_rx: &mut Option<oneshot::Receiver<()>>,
+    commands: &mut Option<mpsc::Receiver<SupervisorCommand>>,
+) -> bool {
+    error!("❌ {}", message);
+    *app_state.install_failure.lock().unwrap() = Some(message.clone());
+    let _ = app_state
+        .event_channel
+        .send(ProgressEvent::new("install-failed", &message, None));
+    app_state.auth.issue_setup_token();
+
+    // Behind the proxy the installer already shows while Foundry is down,
+    // otherwise it takes the public port back until Foundry is reinstalled
+    let installer = if config.builtin_proxy {
+        None
+    } else {
+        match server::start_server(
+            config,
+            app_state.clone(),
+            &config.server_host,
+            config.server_port,
+        ) {
+            Ok((handle, _, _)) => Some(handle),
+            Err(e) => {
+                error!("Failed to start the installer: {}", e);
+                None
+            }
+        }
+    };
+
+    let keep_running = loop {
+        let shutdown = async {
+            match shutdown_rx.as_mut() {
+                Some(rx) => {
+                    let _ = rx.await;
+                }
+                None => std::future::pending::<()>().await,
+            }
+        };
+
+        tokio::select! {
+            _ = app_state.reinstalled.notified() => break true,
+            Some(command) = next_command(commands) => match command {
+                SupervisorCommand::Restart => {
+                    debug!("Ignoring restart request, FoundryVTT is not running")
+                }
+                SupervisorCommand::Upgrade { archive, job } => {
+                    if install_update(config, app_state, &archive, job).await {
+                        app_state.install_failure.lock().unwrap().take();
+                        break true;
+                    }
+                }
+            },
+            _ = shutdown => break false,
+        }
+    };
+
+    if let Some(handle) = installer {
+        handle.stop(true).await;
+    }
+    if keep_running {
+        // The installer extracted Foundry as root, hand it to the Foundry user
+        if let Some(run_as) = config.run_as {
+            privileges::chown_dirs(&[&config.target_dir], run_as);
+        }
+        info!("✅ FoundryVTT was reinstalled, starting it");
+    }
+    keep_running
+}
+
+/// Describes how Foundry exited. A SIGKILL counts as an OOM kill when the
+/// cgroup's OOM kill counter went up while the process was running.
+fn exit_report(exit: ExitStatus, oom_kills_before: Option<u64>) -> ExitReport {
+    let signal = exit.signal();
+    let oom_killed = signal == Some(libc::SIGKILL)
+        && matches!(
+            (oom_kills_before, limits::oom_kill_count()),
+            (Some(before), Some(after)) if after > before
+        );
+
+    ExitReport {
+        at: Utc::now(),
+        code: exit.code(),
+        signal,
+        oom_killed,
+    }
+}
+
+/// Describes an exit for post-exit hooks
+fn hook_exit(reason: &'static str, exit: Option<ExitStatus>) -> HookExit {
+    HookExit {
+        reason,
+        code: exit.and_then(|exit| exit.code()),
+        signal: exit.and_then(|exit| exit.signal()),
+        oom_killed: reason == "oom",
+    }
+}
+
+/// Serves the scheduled-off page until the play window opens.
+/// Returns false when a shutdown was requested in the meantime.
+async fn wait_for_play_window(
+    config: &AppConfig,
+    app_state: &web::Data<AppState>,
+    back_at: Option<DateTime<Local>>,
+    shutdown_rx: &mut Option<oneshot::Receiver<()>>,
+) -> bool {
+    match back_at {
+        Some(at) => info!(
+            "🌙 FoundryVTT is scheduled off until {}",
+            at.format("%Y-%m-%d %H:%M")
+        ),
+        None => warn!("🌙 FoundryVTT is scheduled off and the play window never opens again"),
+    }
+    app_state.supervisor.lock().unwrap().scheduled_off_until = back_at.map(schedule::to_utc);
+    let _ = app_state.event_channel.send(ProgressEvent::new(
+        "schedule",
+        "FoundryVTT is scheduled off",
+        None,
+    ));
+
+    let offline_server = match server::start_offline_server(config, app_state.clone()) {
+        Ok(handle) => Some(handle),
+        Err(e) => {
+            error!("Failed to start scheduled-off page: {}", e);
+            None
+        }
+    };
+
+    let reopened = async {
+        match back_at {
+            Some(at) => sleep_until_local(at).await,
+            None => std::future::pending().await,
+        }
+    };
+    let shutdown = async {
+        match shutdown_rx.as_mut() {
+            Some(rx) => {
+                let _ = rx.await;
+            }
+            None => std::future::pending::<()>().await,
+        }
+    };
+
+    let keep_running = tokio::select! {
+        _ = reopened => true,
+        _ = shutdown => false,
+    };
+
+    if let Some(handle) = offline_server {
+        handle.stop(true).await;
+    }
+    app_state.supervisor.lock().unwrap().scheduled_off_until = None;
+    if keep_running {
+        info!("☀️ Play window opened, starting FoundryVTT");
+    }
+    keep_running
+}
+
+/// Sleeps until the wall-clock time `at`
+async fn sleep_until_local(at: DateTime<Local>) {
+    let remaining = (at - Local::now()).to_std().unwrap_or_default();
+    sleep(remaining).await;
+}
+
+/// Stops Foundry with SIGTERM to its process group, escalating to SIGKILL after a grace period.
+/// Returns the exit status when it could be collected.
+async fn terminate(child: &mut Child) -> Option<ExitStatus> {
+    if let Some(pid) = child.id() {
+        info!("Sending SIGTERM to FoundryVTT process group (PID: {})", pid);
+    }
+
+    if reaper::signal_foundry_group(libc::SIGTERM) {
+        match timeout(TERMINATE_GRACE_PERIOD, child.wait()).await {
+            Ok(Ok(exit)) => return Some(exit),
+            Ok(Err(e)) => error!("Error waiting for FoundryVTT to exit: {}", e),
+            Err(_) => warn!(
+                "FoundryVTT did not exit within {}s, sending SIGKILL",
+                TERMINATE_GRACE_PERIOD.as_secs()
+            ),
+        }
+        reaper::signal_foundry_group(libc::SIGKILL);
+    }
+
+    if let Err(e) = child.kill().await {
+        error!("Failed to kill FoundryVTT process: {}", e);
+    }
+    child.try_wait().ok().flatten()
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::io;
+
+/// cgroup v2 and v1 files that count OOM kills for the container
+const OOM_EVENT_FILES: [&str; 2] = [
+    "/sys/fs/cgroup/memory.events",
+    "/sys/fs/cgroup/memory/memory.oom_control",
+];
+
+/// Resource limits applied to the Foundry child right before it execs
+#[derive(Debug, Clone, Default)]
+pub struct ResourceLimits {
+    /// RLIMIT_AS in bytes
+    pub address_space: Option<u64>,
+    /// RLIMIT_NOFILE
+    pub open_files: Option<u64>,
+    /// RLIMIT_CORE in bytes
+    pub core_size: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        self.address_space.is_none() && self.open_files.is_none() && self.core_size.is_none()
+    }
+
+    /// Applies the configured limits to the calling process.
+    ///
+    /// Runs between fork and exec, so it only calls setrlimit(2) and allocates nothing.
+    pub fn apply(&self) -> io::Result<()> {
+        let limits = [
+            (libc::RLIMIT_AS, self.address_space),
+            (libc::RLIMIT_NOFILE, self.open_files),
+            (libc::RLIMIT_CORE, self.core_size),
+        ];
+
+        for (resource, value) in limits {
+            let Some(value) = value else {
+                continue;
+            };
+            let limit = libc::rlimit {
+                rlim_cur: value as libc::rlim_t,
+                rlim_max: value as libc::rlim_t,
+            };
+            // SAFETY: setrlimit(2) only reads the struct passed by reference
+            if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Total OOM kills recorded for the container's memory cgroup, if the kernel exposes them
+pub fn oom_kill_count() -> Option<u64> {
+    OOM_EVENT_FILES
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+        .and_then(|events| parse_oom_kills(&events))
+}
+
+/// Reads the `oom_kill` counter from `memory.events` or `memory.oom_control`
+fn parse_oom_kills(events: &str) -> Option<u64> {
+    events.lines().find_map(|line| {
+        let (key, value) = line.split_once(' ')?;
+        if key == "oom_kill" {
+            value.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_oom_kills_cgroup_v2() {
+        let events = "low 0\nhigh 0\nmax 12\noom 3\noom_kill 2\noom_group_kill 0\n";
+        assert_eq!(parse_oom_kills(events), Some(2));
+    }
+
+    #[test]
+    fn test_parse_oom_kills_cgroup_v1() {
+        let events = "oom_kill_disable 0\nunder_oom 0\noom_kill 5\n";
+        assert_eq!(parse_oom_kills(events), Some(5));
+    }
+
+    #[test]
+    fn test_parse_oom_kills_missing() {
+        assert_eq!(parse_oom_kills("low 0\nhigh 0\n"), None);
+    }
+}
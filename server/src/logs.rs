@@ -0,0 +1,352 @@
+use actix_web::{Error, HttpResponse, web};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time::interval;
+use tracing::{debug, error, info, warn};
+
+use crate::server::AppState;
+
+/// Output stream a Foundry log line was read from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+impl LogStream {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogStream::Stdout => "stdout",
+            LogStream::Stderr => "stderr",
+        }
+    }
+}
+
+/// Severity Foundry attached to a log line
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    fn from_label(label: &str) -> Option<Self> {
+        match label.trim().to_lowercase().as_str() {
+            "error" | "err" | "fatal" => Some(LogLevel::Error),
+            "warn" | "warning" => Some(LogLevel::Warn),
+            "info" | "notice" => Some(LogLevel::Info),
+            "debug" | "verbose" | "trace" => Some(LogLevel::Debug),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    pub timestamp: DateTime<Utc>,
+    pub stream: LogStream,
+    pub level: LogLevel,
+    pub line: String,
+}
+
+/// Splits a Foundry log line into its level and message.
+///
+/// Foundry prints lines like `FoundryVTT | 2024-05-01 18:35:07 | [info] Running on Node.js`,
+/// while older builds and some dependencies use `[warn] ...` or `error: ...`.
+/// Anything without a recognizable level is treated as info.
+pub fn parse_foundry_line(line: &str) -> (LogLevel, &str) {
+    let mut message = line.trim();
+
+    // Drop the "FoundryVTT | <timestamp> | " prefix
+    if message.starts_with("FoundryVTT |") {
+        let mut parts = message.splitn(3, " | ");
+        if let (Some(_), Some(_), Some(rest)) = (parts.next(), parts.next(), parts.next()) {
+            message = rest.trim_start();
+        }
+    }
+
+    if let Some(rest) = message.strip_prefix('[')
+        && let Some((label, text)) = rest.split_once(']')
+        && let Some(level) = LogLevel::from_label(label)
+    {
+        return (level, text.trim_start());
+    }
+
+    if let Some((label, text)) = message.split_once(':')
+        && let Some(level) = LogLevel::from_label(label)
+    {
+        return (level, text.trim_start());
+    }
+
+    (LogLevel::Info, message)
+}
+
+/// Re-emits a Foundry log line through tracing so it shares the watcher's log pipeline
+fn emit(stream: LogStream, level: LogLevel, message: &str) {
+    let stream = stream.as_str();
+    match level {
+        LogLevel::Error => error!(target: "foundry", source = "foundry", stream, "{}", message),
+        LogLevel::Warn => warn!(target: "foundry", source = "foundry", stream, "{}", message),
+        LogLevel::Info => info!(target: "foundry", source = "foundry", stream, "{}", message),
+        LogLevel::Debug => debug!(target: "foundry", source = "foundry", stream, "{}", message),
+    }
+}
+
+impl LogLine {
+    pub fn to_sse_format(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_else(|e| {
+            error!("Failed to serialize log line: {}", e);
+            "{}".to_string()
+        });
+        format!("data: {}\n\n", json)
+    }
+}
+
+/// Keeps the last `capacity` lines of Foundry output and fans new lines out to subscribers
+pub struct LogBuffer {
+    lines: Mutex<VecDeque<LogLine>>,
+    capacity: usize,
+    live: broadcast::Sender<LogLine>,
+}
+
+pub type SharedLogBuffer = Arc<LogBuffer>;
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        let (live, _) = broadcast::channel(256);
+        Self {
+            lines: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            live,
+        }
+    }
+
+    pub fn push(&self, stream: LogStream, level: LogLevel, line: String) {
+        let entry = LogLine {
+            timestamp: Utc::now(),
+            stream,
+            level,
+            line,
+        };
+
+        // Hold the lock while broadcasting so `subscribe_with_backlog` never misses or repeats a line
+        let mut lines = self.lines.lock().unwrap();
+        if self.capacity > 0 {
+            if lines.len() == self.capacity {
+                lines.pop_front();
+            }
+            lines.push_back(entry.clone());
+        }
+        let _ = self.live.send(entry);
+    }
+
+    /// Returns up to `count` of the most recent lines, oldest first
+    pub fn tail(&self, count: usize) -> Vec<LogLine> {
+        let lines = self.lines.lock().unwrap();
+        lines
+            .iter()
+            .skip(lines.len().saturating_sub(count))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the last `count` lines together with a receiver for everything after them
+    pub fn subscribe_with_backlog(
+        &self,
+        count: usize,
+    ) -> (Vec<LogLine>, broadcast::Receiver<LogLine>) {
+        let lines = self.lines.lock().unwrap();
+        let backlog = lines
+            .iter()
+            .skip(lines.len().saturating_sub(count))
+            .cloned()
+            .collect();
+        (backlog, self.live.subscribe())
+    }
+}
+
+/// Reads `reader` line by line into `buffer`, re-emitting each line as a tracing event.
+/// Bytes that are not UTF-8 are replaced rather than ending the capture, which would
+/// close the pipe and make Foundry's next write fail.
+pub fn capture<R>(reader: R, stream: LogStream, buffer: SharedLogBuffer) -> JoinHandle<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(reader);
+        let mut raw = Vec::new();
+
+        loop {
+            raw.clear();
+            match reader.read_until(b'\n', &mut raw).await {
+                Ok(0) => break,
+                Ok(_) => {
+                    let line = String::from_utf8_lossy(&raw);
+                    let line = line.trim_end_matches(['\n', '\r']).to_string();
+                    let (level, message) = parse_foundry_line(&line);
+                    emit(stream, level, message);
+                    buffer.push(stream, level, line);
+                }
+                Err(e) => {
+                    error!("Failed to read FoundryVTT output: {}", e);
+                    break;
+                }
+            }
+        }
+        debug!("FoundryVTT {:?} stream closed", stream);
+    })
+}
+
+#[derive(Deserialize)]
+pub struct LogsQuery {
+    /// Number of buffered lines to send before streaming live output
+    backlog: Option<usize>,
+}
+
+pub async fn sse_logs(data: web::Data<AppState>, query: web::Query<LogsQuery>) -> HttpResponse {
+    debug!("Client connected to SSE logs endpoint");
+    let (backlog, rx) = data
+        .logs
+        .subscribe_with_backlog(query.backlog.unwrap_or(200));
+
+    let backlog = stream::iter(
+        backlog
+            .into_iter()
+            .map(|line| Ok::<_, Error>(Bytes::from(line.to_sse_format()))),
+    );
+
+    let live = stream::unfold(
+        (rx, interval(Duration::from_secs(15))),
+        |(mut rx, mut keepalive)| async move {
+            loop {
+                tokio::select! {
+                    _ = keepalive.tick() => {
+                        return Some((Ok::<_, Error>(Bytes::from(":\n\n")), (rx, keepalive)));
+                    }
+                    line = rx.recv() => {
+                        match line {
+                            Ok(line) => {
+                                let bytes = Bytes::from(line.to_sse_format());
+                                return Some((Ok::<_, Error>(bytes), (rx, keepalive)));
+                            }
+                            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                                warn!("Log subscriber lagged, skipped {} lines", skipped);
+                            }
+                            Err(broadcast::error::RecvError::Closed) => return None,
+                        }
+                    }
+                }
+            }
+        },
+    );
+
+    HttpResponse::Ok()
+        .insert_header(("Content-Type", "text/event-stream"))
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("Connection", "keep-alive"))
+        .streaming(futures_util::StreamExt::chain(backlog, live))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_buffer_keeps_last_lines() {
+        let buffer = LogBuffer::new(3);
+        for i in 0..5 {
+            buffer.push(LogStream::Stdout, LogLevel::Info, format!("line {}", i));
+        }
+
+        let (backlog, _) = buffer.subscribe_with_backlog(10);
+        let lines: Vec<String> = backlog.into_iter().map(|l| l.line).collect();
+        assert_eq!(lines, vec!["line 2", "line 3", "line 4"]);
+
+        let (backlog, _) = buffer.subscribe_with_backlog(1);
+        let lines: Vec<String> = backlog.into_iter().map(|l| l.line).collect();
+        assert_eq!(lines, vec!["line 4"]);
+    }
+
+    #[test]
+    fn test_log_buffer_backlog_then_live() {
+        let buffer = LogBuffer::new(10);
+        buffer.push(LogStream::Stdout, LogLevel::Info, "before".to_string());
+
+        let (backlog, mut rx) = buffer.subscribe_with_backlog(10);
+        buffer.push(LogStream::Stderr, LogLevel::Error, "after".to_string());
+
+        assert_eq!(backlog.len(), 1);
+        assert_eq!(backlog[0].line, "before");
+        let live = rx.try_recv().unwrap();
+        assert_eq!(live.line, "after");
+        assert_eq!(live.stream, LogStream::Stderr);
+    }
+
+    #[actix_web::test]
+    async fn test_capture_survives_invalid_utf8() {
+        let buffer: SharedLogBuffer = Arc::new(LogBuffer::new(10));
+        let output: &'static [u8] = b"module says \xff\xfe\r\nstill running\n";
+        capture(output, LogStream::Stdout, buffer.clone())
+            .await
+            .unwrap();
+
+        let lines: Vec<String> = buffer.tail(10).into_iter().map(|l| l.line).collect();
+        assert_eq!(lines, vec!["module says \u{fffd}\u{fffd}", "still running"]);
+    }
+
+    #[test]
+    fn test_parse_foundry_line_with_prefix() {
+        assert_eq!(
+            parse_foundry_line("FoundryVTT | 2024-05-01 18:35:07 | [info] Running on Node.js"),
+            (LogLevel::Info, "Running on Node.js")
+        );
+        assert_eq!(
+            parse_foundry_line("FoundryVTT | 2024-05-01 18:35:07 | [warn] Deprecated module"),
+            (LogLevel::Warn, "Deprecated module")
+        );
+        assert_eq!(
+            parse_foundry_line("FoundryVTT | 2024-05-01 18:35:07 | [error] EADDRINUSE"),
+            (LogLevel::Error, "EADDRINUSE")
+        );
+    }
+
+    #[test]
+    fn test_parse_foundry_line_short_forms() {
+        assert_eq!(
+            parse_foundry_line("[WARN] low disk"),
+            (LogLevel::Warn, "low disk")
+        );
+        assert_eq!(
+            parse_foundry_line("error: something broke"),
+            (LogLevel::Error, "something broke")
+        );
+        assert_eq!(
+            parse_foundry_line("debug: socket opened"),
+            (LogLevel::Debug, "socket opened")
+        );
+    }
+
+    #[test]
+    fn test_parse_foundry_line_unparseable_is_info() {
+        assert_eq!(
+            parse_foundry_line("    at Object.<anonymous> (main.js:1:1)"),
+            (LogLevel::Info, "at Object.<anonymous> (main.js:1:1)")
+        );
+        assert_eq!(
+            parse_foundry_line("Note: not a level"),
+            (LogLevel::Info, "Note: not a level")
+        );
+    }
+}
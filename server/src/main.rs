@@ -1,64 +1,175 @@
+mod auth;
+mod backup;
 mod config;
 mod downloader;
 mod events;
 mod extractor;
+mod forward_auth;
 mod handlers;
+mod hooks;
 mod initialization;
+mod install_check;
+mod jobs;
 mod launch;
+mod limits;
+mod logs;
+mod metrics;
+mod options;
+mod ports;
+mod privileges;
+mod provision;
+mod proxy;
+mod readiness;
+mod reaper;
+mod schedule;
 mod server;
+mod status;
+mod tls;
+mod upgrade;
 mod utils;
+mod watchdog;
+mod worlds;
 
+use crate::proxy::Upstreams;
+use crate::server::AppState;
 use crate::utils::paths;
 use tokio::sync::oneshot;
-use tracing::{Level, error, info};
+use tracing::{Level, error, info, warn};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    // Initialize tracing with a more verbose default level
-    tracing_subscriber::fmt()
+    // RUST_LOG narrows or widens the output; without it Foundry's info lines still reach stdout
+    let directives = std::env::var("RUST_LOG")
+        .ok()
+        .filter(|directives| !directives.trim().is_empty())
+        .unwrap_or_else(|| "info".to_string());
+    let subscriber = tracing_subscriber::fmt()
         .with_max_level(Level::DEBUG)
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .with_writer(std::io::stdout)
-        .init();
+        .with_env_filter(tracing_subscriber::EnvFilter::new(&directives))
+        .with_writer(std::io::stdout);
 
-    info!("Logging initialized at DEBUG level");
+    // LOG_FORMAT=json emits one JSON object per line for log pipelines
+    if std::env::var("LOG_FORMAT").is_ok_and(|format| format.eq_ignore_ascii_case("json")) {
+        subscriber.json().init();
+    } else {
+        subscriber.init();
+    }
+
+    info!("Logging initialized with filter {}", directives);
+
+    // Act as a minimal init: forward signals to Foundry and reap orphans when PID 1
+    reaper::setup_signal_handlers();
+    reaper::spawn_reaper();
 
     // Load application configuration
-    let app_config = config::AppConfig::from_env();
+    let mut app_config = config::AppConfig::from_env();
 
     // Run initialization checks and setup from the old run.sh
-    if let Err(e) = initialization::initialize(&app_config) {
+    if let Err(e) = initialization::initialize(&mut app_config) {
         error!("Initialization failed: {}", e);
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            e.to_string(),
-        ));
+        return Err(std::io::Error::other(e.to_string()));
     }
 
-    // Check if we should directly launch Foundry
-    if paths::FOUNDRY_SCRIPT_PATH.exists() {
-        info!("Foundry main.js detected, skipping Actix server and launching Foundry directly");
-        launch::launch_foundry_process(None, &app_config).await;
-        return Ok(());
-    }
+    let auth = match auth::Auth::from_env() {
+        Ok(auth) => auth,
+        Err(e) => {
+            error!("Failed to read the watcher credentials: {:#}", e);
+            return Err(std::io::Error::other(e.to_string()));
+        }
+    };
 
-    // Log configuration settings
-    info!("Serving static files from: {}", app_config.static_files_dir);
-    info!("Downloading files to: {}", app_config.target_dir);
+    let tls = match app_config.tls.as_ref().map(tls::server_config).transpose() {
+        Ok(tls) => tls,
+        Err(e) => {
+            error!("Failed to set up TLS: {:#}", e);
+            return Err(std::io::Error::other(e.to_string()));
+        }
+    };
+
+    // State shared between the HTTP server and the Foundry supervisor
+    let app_state = AppState::new(&app_config, auth);
+
+    // The management API stays up on its own port whatever happens to Foundry
+    server::start_management_server(&app_config, app_state.clone(), tls.clone())?;
+
+    let installed = paths::FOUNDRY_SCRIPT_PATH.exists();
+
+    // Signalled by the installer once Foundry has been extracted. An installed
+    // Foundry that turns out broken is reported to the supervisor instead.
+    let (installed_tx, installed_rx) = oneshot::channel::<()>();
+    if !installed {
+        *app_state.shutdown_sender.lock().unwrap() = Some(installed_tx);
+        app_state.auth.issue_setup_token();
+    } else if !app_state.auth.is_configured() {
+        warn!(
+            "⚠️ No AUTH_TOKEN or AUTH_USERNAME and AUTH_PASSWORD set, the management API is locked"
+        );
+    }
 
     // Create a channel for shutting down Foundry when needed
     let (_foundry_tx, foundry_rx) = oneshot::channel::<()>();
 
-    // Start the HTTP server
-    let server_handle = server::start_server(&app_config).await?;
+    if app_config.builtin_proxy {
+        // The installer UI moves to a loopback port and the proxy shows it
+        // whenever Foundry is not accepting connections
+        let (_, ui_port, _) = server::start_server(&app_config, app_state.clone(), "127.0.0.1", 0)?;
+        proxy::start_proxy(
+            &app_config.server_host,
+            app_config.server_port,
+            Upstreams {
+                foundry: app_config.foundry_port,
+                fallback: ui_port,
+            },
+            tls,
+        )
+        .await?;
+
+        if !installed {
+            info!("Serving static files from: {}", app_config.static_files_dir);
+            info!("Downloading files to: {}", app_config.target_dir);
+            let _ = installed_rx.await;
+        }
+    } else if !installed {
+        // Log configuration settings
+        info!("Serving static files from: {}", app_config.static_files_dir);
+        info!("Downloading files to: {}", app_config.target_dir);
+
+        // Start the HTTP server
+        let (server_handle, _, server_task) = server::start_server(
+            &app_config,
+            app_state.clone(),
+            &app_config.server_host,
+            app_config.server_port,
+        )?;
+
+        // Stop the installer once Foundry is installed so Foundry can take over its port
+        tokio::spawn(async move {
+            if installed_rx.await.is_ok() {
+                info!("Received shutdown signal, stopping Actix server");
+                server_handle.stop(true).await;
+                info!("Actix server stopped, transitioning to process management mode");
+            }
+        });
 
-    // Wait for the server to complete (after receiving shutdown signal)
-    // Fix: Explicitly acknowledge the Result with let _
-    let _ = server_handle.await?;
-    info!("Actix server has terminated, launching Foundry VTT");
+        // Wait for the server to complete (after receiving shutdown signal). Once it has
+        // returned, its sockets are closed and Foundry can bind the public port.
+        // Fix: Explicitly acknowledge the Result with let _
+        let _ = server_task.await?;
+        info!("Actix server has terminated");
+    }
+
+    if installed {
+        info!("Foundry main.js detected, skipping the installer and launching Foundry directly");
+    } else {
+        info!("Installation complete, launching Foundry VTT");
+        // The installer extracted Foundry as root, hand it to the Foundry user
+        if let Some(run_as) = app_config.run_as {
+            privileges::chown_dirs(&[&app_config.target_dir], run_as);
+        }
+    }
 
-    // After server stops, launch Foundry directly with the shutdown channel
-    launch::launch_foundry_process(Some(foundry_rx), &app_config).await;
+    // Launch Foundry with the shutdown channel
+    launch::launch_foundry_process(Some(foundry_rx), &app_config, app_state).await;
 
     Ok(())
 }
@@ -0,0 +1,307 @@
+use actix_web::{HttpResponse, Responder, web};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::readiness::ReadinessState;
+use crate::server::AppState;
+use crate::utils::procfs;
+
+const READINESS_STATES: [ReadinessState; 5] = [
+    ReadinessState::Stopped,
+    ReadinessState::Starting,
+    ReadinessState::Listening,
+    ReadinessState::Ready,
+    ReadinessState::Degraded,
+];
+
+/// Number and total length of timed operations
+#[derive(Debug, Default, Clone, Copy)]
+struct Timing {
+    count: u64,
+    seconds: f64,
+}
+
+impl Timing {
+    fn add(&mut self, elapsed: Duration) {
+        self.count += 1;
+        self.seconds += elapsed.as_secs_f64();
+    }
+}
+
+/// Counters collected while the watcher runs. Gauges such as readiness and the
+/// Foundry process' memory are read when `/metrics` is scraped.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Keyed by kind (install or upgrade) and outcome
+    install_jobs: Mutex<BTreeMap<(&'static str, &'static str), u64>>,
+    download_bytes: AtomicU64,
+    downloads: Mutex<Timing>,
+    upload_bytes: AtomicU64,
+    uploads: Mutex<Timing>,
+    extractions: Mutex<Timing>,
+    foundry_restarts: AtomicU64,
+    /// Keyed by why the process ended and its exit code or signal
+    foundry_exits: Mutex<BTreeMap<(&'static str, String), u64>>,
+    sse_subscribers: AtomicU64,
+    sse_dropped_events: AtomicU64,
+}
+
+impl Metrics {
+    pub fn record_install(&self, kind: &'static str, outcome: &'static str) {
+        *self
+            .install_jobs
+            .lock()
+            .unwrap()
+            .entry((kind, outcome))
+            .or_default() += 1;
+    }
+
+    pub fn record_download(&self, bytes: u64, elapsed: Duration) {
+        self.download_bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.downloads.lock().unwrap().add(elapsed);
+    }
+
+    pub fn record_upload(&self, bytes: u64, elapsed: Duration) {
+        self.upload_bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.uploads.lock().unwrap().add(elapsed);
+    }
+
+    pub fn record_extraction(&self, elapsed: Duration) {
+        self.extractions.lock().unwrap().add(elapsed);
+    }
+
+    pub fn record_restart(&self) {
+        self.foundry_restarts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a Foundry process that ended, by exit code or, when killed, by signal
+    pub fn record_exit(&self, reason: &'static str, code: Option<i32>, signal: Option<i32>) {
+        let code = match (code, signal) {
+            (Some(code), _) => code.to_string(),
+            (None, Some(signal)) => format!("signal {}", signal),
+            (None, None) => "unknown".to_string(),
+        };
+        *self
+            .foundry_exits
+            .lock()
+            .unwrap()
+            .entry((reason, code))
+            .or_default() += 1;
+    }
+
+    pub fn sse_connected(&self) {
+        self.sse_subscribers.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn sse_disconnected(&self) {
+        self.sse_subscribers.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Counts events a slow SSE client missed because the channel moved on without it
+    pub fn record_dropped_events(&self, count: u64) {
+        self.sse_dropped_events.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Renders every metric in the Prometheus text exposition format
+    pub fn render(&self, app_state: &AppState) -> String {
+        let mut out = Exposition::default();
+
+        out.family(
+            "foundry_watcher_install_jobs_total",
+            "counter",
+            "Installs and upgrades of FoundryVTT by outcome",
+        );
+        for ((kind, outcome), count) in self.install_jobs.lock().unwrap().iter() {
+            out.sample(
+                "foundry_watcher_install_jobs_total",
+                &[("kind", kind), ("outcome", outcome)],
+                count,
+            );
+        }
+
+        out.counter(
+            "foundry_watcher_download_bytes_total",
+            "Bytes of FoundryVTT archives downloaded",
+            self.download_bytes.load(Ordering::Relaxed),
+        );
+        out.timing(
+            "foundry_watcher_download_duration_seconds",
+            "Time spent on completed downloads",
+            *self.downloads.lock().unwrap(),
+        );
+        out.counter(
+            "foundry_watcher_upload_bytes_total",
+            "Bytes of FoundryVTT archives uploaded to the installer",
+            self.upload_bytes.load(Ordering::Relaxed),
+        );
+        out.timing(
+            "foundry_watcher_upload_duration_seconds",
+            "Time spent on completed uploads",
+            *self.uploads.lock().unwrap(),
+        );
+        out.timing(
+            "foundry_watcher_extraction_duration_seconds",
+            "Time spent on completed archive extractions",
+            *self.extractions.lock().unwrap(),
+        );
+
+        let pid = app_state.supervisor.lock().unwrap().pid;
+        out.gauge(
+            "foundry_watcher_foundry_up",
+            "Whether a FoundryVTT process is running",
+            u8::from(pid.is_some()),
+        );
+        out.counter(
+            "foundry_watcher_foundry_restarts_total",
+            "Times FoundryVTT was started again after its process ended",
+            self.foundry_restarts.load(Ordering::Relaxed),
+        );
+        out.family(
+            "foundry_watcher_foundry_exits_total",
+            "counter",
+            "FoundryVTT processes that ended, by reason and exit code or signal",
+        );
+        for ((reason, code), count) in self.foundry_exits.lock().unwrap().iter() {
+            out.sample(
+                "foundry_watcher_foundry_exits_total",
+                &[("reason", reason), ("code", code)],
+                count,
+            );
+        }
+
+        let state = app_state.readiness.state();
+        out.family(
+            "foundry_watcher_readiness_state",
+            "gauge",
+            "Readiness of FoundryVTT, 1 for the current state",
+        );
+        for candidate in READINESS_STATES {
+            out.sample(
+                "foundry_watcher_readiness_state",
+                &[("state", candidate.as_str())],
+                u8::from(candidate == state),
+            );
+        }
+
+        if let Some(pid) = pid {
+            if let Some(rss_kb) = procfs::rss_kb(pid) {
+                out.gauge(
+                    "foundry_watcher_foundry_resident_memory_bytes",
+                    "Resident memory of the FoundryVTT process",
+                    rss_kb * 1024,
+                );
+            }
+            if let Some(cpu) = procfs::cpu_seconds(pid) {
+                out.counter(
+                    "foundry_watcher_foundry_cpu_seconds_total",
+                    "CPU time used by the current FoundryVTT process",
+                    cpu,
+                );
+            }
+        }
+
+        out.gauge(
+            "foundry_watcher_sse_subscribers",
+            "Clients connected to the event stream",
+            self.sse_subscribers.load(Ordering::Relaxed),
+        );
+        out.counter(
+            "foundry_watcher_sse_dropped_events_total",
+            "Events skipped for clients that fell behind the event stream",
+            self.sse_dropped_events.load(Ordering::Relaxed),
+        );
+
+        out.0
+    }
+}
+
+/// Builds a Prometheus text exposition
+#[derive(Default)]
+struct Exposition(String);
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.0, "# HELP {} {}", name, help);
+        let _ = writeln!(self.0, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        self.0.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(label, value)| format!("{}=\"{}\"", label, escape_label(value)))
+                .collect();
+            let _ = write!(self.0, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.0, " {}", value);
+    }
+
+    fn counter(&mut self, name: &str, help: &str, value: impl Display) {
+        self.family(name, "counter", help);
+        self.sample(name, &[], value);
+    }
+
+    fn gauge(&mut self, name: &str, help: &str, value: impl Display) {
+        self.family(name, "gauge", help);
+        self.sample(name, &[], value);
+    }
+
+    /// A summary without quantiles, which is enough to graph average durations
+    fn timing(&mut self, name: &str, help: &str, timing: Timing) {
+        self.family(name, "summary", help);
+        self.sample(&format!("{}_sum", name), &[], timing.seconds);
+        self.sample(&format!("{}_count", name), &[], timing.count);
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Serves the metrics for Prometheus
+pub async fn metrics(app_state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(app_state.metrics.render(&app_state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exposition_format() {
+        let mut out = Exposition::default();
+        out.family("jobs_total", "counter", "Jobs");
+        out.sample(
+            "jobs_total",
+            &[("kind", "install"), ("outcome", "say \"hi\"")],
+            2,
+        );
+        out.timing(
+            "took_seconds",
+            "Time",
+            Timing {
+                count: 2,
+                seconds: 1.5,
+            },
+        );
+        assert_eq!(
+            out.0,
+            "# HELP jobs_total Jobs\n\
+             # TYPE jobs_total counter\n\
+             jobs_total{kind=\"install\",outcome=\"say \\\"hi\\\"\"} 2\n\
+             # HELP took_seconds Time\n\
+             # TYPE took_seconds summary\n\
+             took_seconds_sum 1.5\n\
+             took_seconds_count 2\n"
+        );
+    }
+}
@@ -0,0 +1,459 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::utils::files;
+
+/// Command line flags the supervisor manages itself, with the variable that sets them.
+/// `--port` is missing because its variable depends on the proxy mode.
+const MANAGED_FLAGS: [(&str, &str); 11] = [
+    ("--dataPath", "DATA_DIR"),
+    ("--hostname", "APPLICATION_HOST"),
+    ("--proxySSL", "SSL_PROXY"),
+    ("--proxyPort", "PROXY_PORT"),
+    ("--routePrefix", "ROUTE_PREFIX"),
+    ("--sslCert", "SSL_CERT"),
+    ("--sslKey", "SSL_KEY"),
+    ("--language", "LANGUAGE"),
+    ("--world", "FOUNDRY_WORLD"),
+    ("--upnp", "UPNP"),
+    ("--noupnp", "UPNP"),
+];
+
+/// Settings in Config/options.json that mirror the launch options
+const OPTIONS_JSON_KEYS: [&str; 13] = [
+    "port",
+    "dataPath",
+    "hostname",
+    "routePrefix",
+    "proxySSL",
+    "proxyPort",
+    "sslCert",
+    "sslKey",
+    "upnp",
+    "language",
+    "world",
+    "compressStatic",
+    "compressSocket",
+];
+
+/// Settings that always follow the watcher because the container is wired around them
+const WATCHER_OWNED_KEYS: [&str; 2] = ["port", "dataPath"];
+
+/// Number of options.json backups kept next to the file
+const OPTIONS_JSON_BACKUPS: usize = 5;
+
+/// Launch options passed to Foundry's main.js
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FoundryOptions {
+    pub data_path: String,
+    pub port: u16,
+    pub hostname: Option<String>,
+    /// Foundry runs behind a proxy that terminates TLS
+    pub proxy_ssl: bool,
+    /// Port clients connect to on the proxy
+    pub proxy_port: Option<u16>,
+    /// Path prefix without surrounding slashes, e.g. `foundry`
+    pub route_prefix: Option<String>,
+    pub ssl_cert: Option<String>,
+    pub ssl_key: Option<String>,
+    pub language: Option<String>,
+    /// World launched on startup
+    pub world: Option<String>,
+    pub upnp: bool,
+    /// Only written to options.json, Foundry has no command line flags for these
+    pub compress_static: Option<bool>,
+    pub compress_socket: Option<bool>,
+    /// Appended verbatim after the generated flags
+    pub extra_args: Vec<String>,
+}
+
+impl FoundryOptions {
+    /// Checks the options for combinations Foundry would reject or silently ignore.
+    /// `port_var` names the variable Foundry's port is set from.
+    pub fn validate(&self, port_var: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.proxy_port == Some(0) {
+            errors.push("PROXY_PORT must be between 1 and 65535".to_string());
+        }
+
+        if let Some(prefix) = &self.route_prefix
+            && !prefix
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_./~".contains(c))
+        {
+            errors.push(format!(
+                "ROUTE_PREFIX may only contain letters, digits and -_./~: {}",
+                prefix
+            ));
+        }
+
+        match (&self.ssl_cert, &self.ssl_key) {
+            (Some(_), None) | (None, Some(_)) => {
+                errors.push("SSL_CERT and SSL_KEY must be set together".to_string());
+            }
+            (Some(cert), Some(key)) => {
+                for (var, path) in [("SSL_CERT", cert), ("SSL_KEY", key)] {
+                    if !Path::new(path).is_file() {
+                        errors.push(format!("{} does not point to a file: {}", var, path));
+                    }
+                }
+                if self.proxy_ssl {
+                    errors.push(
+                        "SSL_PROXY cannot be combined with SSL_CERT/SSL_KEY, pick one place to terminate TLS"
+                            .to_string(),
+                    );
+                }
+            }
+            (None, None) => {}
+        }
+
+        for (var, value) in [("LANGUAGE", &self.language), ("FOUNDRY_WORLD", &self.world)] {
+            if let Some(value) = value
+                && (value.is_empty() || value.contains(char::is_whitespace) || value.contains('/'))
+            {
+                errors.push(format!("{} is not a valid identifier: {:?}", var, value));
+            }
+        }
+
+        for arg in &self.extra_args {
+            let flag = arg.split('=').next().unwrap_or(arg);
+            let var = match flag {
+                "--port" => Some(port_var),
+                _ => MANAGED_FLAGS
+                    .iter()
+                    .find(|(managed, _)| *managed == flag)
+                    .map(|(_, var)| *var),
+            };
+            if let Some(var) = var {
+                errors.push(format!(
+                    "FOUNDRY_EXTRA_ARGS sets {}, use {} instead",
+                    flag, var
+                ));
+            }
+        }
+
+        errors
+    }
+
+    /// Arguments for Foundry's main.js in the order Foundry documents them
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![
+            format!("--dataPath={}", self.data_path),
+            format!("--port={}", self.port),
+        ];
+        if let Some(hostname) = &self.hostname {
+            args.push(format!("--hostname={}", hostname));
+        }
+        if !self.upnp {
+            args.push("--noupnp".to_string());
+        }
+        if self.proxy_ssl {
+            args.push("--proxySSL".to_string());
+        }
+        if let Some(port) = self.proxy_port {
+            args.push(format!("--proxyPort={}", port));
+        }
+        if let Some(prefix) = &self.route_prefix {
+            args.push(format!("--routePrefix={}", prefix));
+        }
+        if let Some(cert) = &self.ssl_cert {
+            args.push(format!("--sslCert={}", cert));
+        }
+        if let Some(key) = &self.ssl_key {
+            args.push(format!("--sslKey={}", key));
+        }
+        if let Some(language) = &self.language {
+            args.push(format!("--language={}", language));
+        }
+        if let Some(world) = &self.world {
+            args.push(format!("--world={}", world));
+        }
+        args.extend(self.extra_args.iter().cloned());
+        args
+    }
+
+    /// Value of `key` as stored in options.json, or None when the watcher leaves it alone.
+    /// Unset preferences (language, world, compression) stay under Foundry's control.
+    fn setting(&self, key: &str) -> Option<Value> {
+        match key {
+            "port" => Some(json!(self.port)),
+            "dataPath" => Some(json!(self.data_path)),
+            "hostname" => Some(json!(self.hostname)),
+            "routePrefix" => Some(json!(self.route_prefix)),
+            "proxySSL" => Some(json!(self.proxy_ssl)),
+            "proxyPort" => Some(json!(self.proxy_port)),
+            "sslCert" => Some(json!(self.ssl_cert)),
+            "sslKey" => Some(json!(self.ssl_key)),
+            "upnp" => Some(json!(self.upnp)),
+            "language" => self.language.as_ref().map(|language| json!(language)),
+            "world" => self.world.as_ref().map(|world| json!(world)),
+            "compressStatic" => self.compress_static.map(Value::from),
+            "compressSocket" => self.compress_socket.map(Value::from),
+            _ => None,
+        }
+    }
+
+    /// Takes over a value from options.json, ignoring values of the wrong type
+    fn set_setting(&mut self, key: &str, value: &Value) {
+        let string = || value.as_str().map(str::to_string);
+        match key {
+            "hostname" => self.hostname = string(),
+            "routePrefix" => self.route_prefix = value.as_str().and_then(normalize_route_prefix),
+            "proxyPort" => self.proxy_port = value.as_u64().and_then(|port| port.try_into().ok()),
+            "sslCert" => self.ssl_cert = string(),
+            "sslKey" => self.ssl_key = string(),
+            "language" => self.language = string(),
+            "world" => self.world = string(),
+            "proxySSL" => self.proxy_ssl = value.as_bool().unwrap_or(self.proxy_ssl),
+            "upnp" => self.upnp = value.as_bool().unwrap_or(self.upnp),
+            "compressStatic" => self.compress_static = value.as_bool(),
+            "compressSocket" => self.compress_socket = value.as_bool(),
+            _ => {}
+        }
+    }
+
+    /// URL of Foundry's `/api/status` endpoint as seen from inside the container
+    pub fn status_url(&self) -> String {
+        let scheme = if self.ssl_cert.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+        let prefix = self
+            .route_prefix
+            .as_ref()
+            .map(|prefix| format!("/{}", prefix))
+            .unwrap_or_default();
+        format!("{}://127.0.0.1:{}{}/api/status", scheme, self.port, prefix)
+    }
+}
+
+/// Which side wins when options.json and the watcher configuration disagree
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptionsPrecedence {
+    /// Environment variables are written into options.json
+    Env,
+    /// Values in options.json are used for the launch, missing ones are filled in
+    File,
+    /// options.json is left alone
+    Off,
+}
+
+impl FromStr for OptionsPrecedence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "env" => Ok(OptionsPrecedence::Env),
+            "file" => Ok(OptionsPrecedence::File),
+            "off" => Ok(OptionsPrecedence::Off),
+            other => Err(format!("unknown options.json precedence: {}", other)),
+        }
+    }
+}
+
+/// A setting whose value in options.json differs from the watcher configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct Drift {
+    pub key: &'static str,
+    /// None when options.json does not have the setting at all
+    pub file: Option<Value>,
+    pub config: Value,
+    /// The value from options.json was kept and used for the launch
+    pub file_wins: bool,
+}
+
+/// Location of Foundry's server settings below the data directory
+pub fn options_json_path(data_dir: &str) -> PathBuf {
+    Path::new(data_dir).join("Config").join("options.json")
+}
+
+/// Reads options.json, treating a missing file as empty
+pub fn read_options_json(path: &Path) -> Result<Map<String, Value>> {
+    if !path.exists() {
+        return Ok(Map::new());
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("{} is not a JSON object", path.display()))
+}
+
+/// Merges the launch options into the parsed options.json. Where the configuration
+/// wins `file` is updated, where the file wins `options` is. Returns every difference.
+pub fn reconcile(
+    file: &mut Map<String, Value>,
+    options: &mut FoundryOptions,
+    precedence: OptionsPrecedence,
+) -> Vec<Drift> {
+    let mut drifts = Vec::new();
+    for key in OPTIONS_JSON_KEYS {
+        let Some(config) = options.setting(key) else {
+            continue;
+        };
+        let current = file.get(key).cloned();
+        if current.as_ref().unwrap_or(&Value::Null) == &config {
+            continue;
+        }
+
+        let file_value = current.as_ref().filter(|value| !value.is_null());
+        let file_wins = precedence == OptionsPrecedence::File
+            && file_value.is_some()
+            && !WATCHER_OWNED_KEYS.contains(&key);
+        if let Some(value) = file_value.filter(|_| file_wins) {
+            options.set_setting(key, value);
+        } else {
+            file.insert(key.to_string(), config.clone());
+        }
+        drifts.push(Drift {
+            key,
+            file: current,
+            config,
+            file_wins,
+        });
+    }
+    drifts
+}
+
+/// Writes options.json the way Foundry does, keeping a timestamped backup of the previous file
+pub fn write_options_json(path: &Path, options: &Map<String, Value>) -> Result<()> {
+    let content = serde_json::to_string_pretty(options)?;
+    files::write_with_backup(path, &content, OPTIONS_JSON_BACKUPS)
+}
+
+/// Trims slashes from a route prefix, treating an empty prefix as unset
+pub fn normalize_route_prefix(prefix: &str) -> Option<String> {
+    let prefix = prefix.trim().trim_matches('/');
+    (!prefix.is_empty()).then(|| prefix.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> FoundryOptions {
+        FoundryOptions {
+            data_path: "/foundrydata".to_string(),
+            port: 4444,
+            hostname: Some("foundry.vtt".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default_args() {
+        assert_eq!(
+            options().args(),
+            vec![
+                "--dataPath=/foundrydata",
+                "--port=4444",
+                "--hostname=foundry.vtt",
+                "--noupnp"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_full_args() {
+        let options = FoundryOptions {
+            proxy_ssl: true,
+            proxy_port: Some(443),
+            route_prefix: Some("vtt".to_string()),
+            language: Some("de.core".to_string()),
+            world: Some("my-campaign".to_string()),
+            upnp: true,
+            extra_args: vec!["--demo".to_string()],
+            ..options()
+        };
+        assert_eq!(
+            options.args(),
+            vec![
+                "--dataPath=/foundrydata",
+                "--port=4444",
+                "--hostname=foundry.vtt",
+                "--proxySSL",
+                "--proxyPort=443",
+                "--routePrefix=vtt",
+                "--language=de.core",
+                "--world=my-campaign",
+                "--demo"
+            ]
+        );
+        assert!(options.validate("APPLICATION_PORT").is_empty());
+        assert_eq!(options.status_url(), "http://127.0.0.1:4444/vtt/api/status");
+    }
+
+    #[test]
+    fn test_validate_rejects_conflicts() {
+        let options = FoundryOptions {
+            ssl_cert: Some("/missing/cert.pem".to_string()),
+            world: Some("two words".to_string()),
+            extra_args: vec!["--port=30000".to_string(), "--noupnp".to_string()],
+            ..options()
+        };
+        let errors = options.validate("FOUNDRY_INTERNAL_PORT");
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert!(errors[0].contains("SSL_CERT and SSL_KEY"));
+        assert!(errors[2].contains("use FOUNDRY_INTERNAL_PORT"));
+        assert!(errors[3].contains("UPNP"));
+    }
+
+    #[test]
+    fn test_reconcile_env_wins() {
+        let mut file: Map<String, Value> = serde_json::from_str(
+            r#"{"port":30000,"hostname":"old.example","routePrefix":null,"upnp":false,"language":"fr.core","cssTheme":"dark"}"#,
+        )
+        .unwrap();
+        let mut options = options();
+        let drifts = reconcile(&mut file, &mut options, OptionsPrecedence::Env);
+
+        let keys: Vec<&str> = drifts.iter().map(|drift| drift.key).collect();
+        assert_eq!(keys, vec!["port", "dataPath", "hostname", "proxySSL"]);
+        assert!(drifts.iter().all(|drift| !drift.file_wins));
+        assert_eq!(file["port"], json!(4444));
+        assert_eq!(file["hostname"], json!("foundry.vtt"));
+        // Unmanaged settings and unset preferences are kept
+        assert_eq!(file["language"], json!("fr.core"));
+        assert_eq!(file["cssTheme"], json!("dark"));
+    }
+
+    #[test]
+    fn test_reconcile_file_wins() {
+        let mut file: Map<String, Value> = serde_json::from_str(
+            r#"{"port":30000,"dataPath":"/foundrydata","hostname":"vtt.example","routePrefix":"/games/","proxySSL":true,"upnp":false}"#,
+        )
+        .unwrap();
+        let mut options = options();
+        let drifts = reconcile(&mut file, &mut options, OptionsPrecedence::File);
+
+        // The port stays with the watcher, everything else follows the file
+        assert!(
+            !drifts
+                .iter()
+                .find(|drift| drift.key == "port")
+                .unwrap()
+                .file_wins
+        );
+        assert_eq!(file["port"], json!(4444));
+        assert_eq!(options.hostname.as_deref(), Some("vtt.example"));
+        assert_eq!(options.route_prefix.as_deref(), Some("games"));
+        assert!(options.proxy_ssl);
+        assert_eq!(file["hostname"], json!("vtt.example"));
+    }
+
+    #[test]
+    fn test_normalize_route_prefix() {
+        assert_eq!(normalize_route_prefix("/vtt/"), Some("vtt".to_string()));
+        assert_eq!(
+            normalize_route_prefix("games/foundry"),
+            Some("games/foundry".to_string())
+        );
+        assert_eq!(normalize_route_prefix("/"), None);
+    }
+}
@@ -0,0 +1,93 @@
+use std::io;
+use std::net::TcpListener;
+use tokio::time::{Duration, Instant, sleep};
+use tracing::{debug, info};
+
+use crate::utils::procfs;
+
+/// How often a busy port is checked again while waiting for it to be released
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Returns true when nothing listens on `port`. Foundry listens on every interface,
+/// so that is what is tried.
+pub fn is_free(port: u16) -> bool {
+    TcpListener::bind(("0.0.0.0", port)).is_ok()
+}
+
+/// Waits up to `timeout` for `port` to be released, e.g. by the installer that just
+/// stopped. Returns a description of whoever still holds it when it is not.
+pub async fn wait_until_free(port: u16, timeout: Duration) -> Result<(), String> {
+    let deadline = Instant::now() + timeout;
+    let mut waited = false;
+    loop {
+        if is_free(port) {
+            if waited {
+                info!("Port {} was released", port);
+            }
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(describe_conflict(port));
+        }
+        if !waited {
+            debug!(
+                "Port {} is still in use, waiting for it to be released",
+                port
+            );
+            waited = true;
+        }
+        sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Names the process listening on `port` as far as /proc tells
+pub fn describe_conflict(port: u16) -> String {
+    let owner = procfs::listening_socket_inodes(port)
+        .into_iter()
+        .find_map(procfs::socket_owner);
+    match owner {
+        Some((pid, _)) if pid == std::process::id() => {
+            format!(
+                "port {} is in use by the watcher itself (PID {})",
+                port, pid
+            )
+        }
+        Some((pid, name)) => format!("port {} is in use by PID {} ({})", port, pid, name),
+        None => format!("port {} is in use by another process", port),
+    }
+}
+
+/// Adds the owner of the port to an address-in-use error from binding it
+pub fn bind_error(port: u16, error: io::Error) -> io::Error {
+    if error.kind() == io::ErrorKind::AddrInUse {
+        io::Error::new(
+            error.kind(),
+            format!("{}: {}", error, describe_conflict(port)),
+        )
+    } else {
+        error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conflict_names_the_listener() {
+        let listener = TcpListener::bind(("0.0.0.0", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(!is_free(port));
+        assert_eq!(
+            describe_conflict(port),
+            format!(
+                "port {} is in use by the watcher itself (PID {})",
+                port,
+                std::process::id()
+            )
+        );
+
+        drop(listener);
+        assert!(is_free(port));
+    }
+}
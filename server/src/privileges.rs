@@ -0,0 +1,108 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs::{MetadataExt, lchown};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+/// Account Foundry is started as when the watcher itself runs as root
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunAs {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl RunAs {
+    /// Switches the calling process to this account with no supplementary groups.
+    ///
+    /// Runs between fork and exec, so it only makes raw syscalls and allocates nothing.
+    pub fn apply(&self) -> io::Result<()> {
+        // SAFETY: setgroups(2), setgid(2) and setuid(2) take no pointers except the
+        // empty group list. The group must change before the uid gives up root.
+        unsafe {
+            if libc::setgroups(0, std::ptr::null()) != 0
+                || libc::setgid(self.gid as libc::gid_t) != 0
+                || libc::setuid(self.uid as libc::uid_t) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Home directory of the account according to /etc/passwd
+    pub fn home_dir(&self) -> Option<String> {
+        let passwd = fs::read_to_string("/etc/passwd").ok()?;
+        passwd_home(&passwd, self.uid)
+    }
+}
+
+/// Returns true when the watcher runs with root privileges
+pub fn is_root() -> bool {
+    // SAFETY: geteuid(2) cannot fail
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Hands every directory in `dirs` to `run_as`, logging instead of failing on errors
+pub fn chown_dirs(dirs: &[&str], run_as: RunAs) {
+    for dir in dirs {
+        match fix_ownership(Path::new(dir), run_as) {
+            Ok(0) => debug!(
+                "Ownership of {} is already {}:{}",
+                dir, run_as.uid, run_as.gid
+            ),
+            Ok(changed) => info!(
+                "🔑 Changed ownership of {} entries in {} to {}:{}",
+                changed, dir, run_as.uid, run_as.gid
+            ),
+            Err(e) => warn!("⚠️ Failed to change ownership of {}: {}", dir, e),
+        }
+    }
+}
+
+/// Recursively changes the owner of `root` and everything below it, without
+/// following symlinks. Returns the number of entries that were changed.
+fn fix_ownership(root: &Path, run_as: RunAs) -> io::Result<usize> {
+    let mut changed = 0;
+    let mut pending: Vec<PathBuf> = vec![root.to_path_buf()];
+
+    while let Some(path) = pending.pop() {
+        let metadata = fs::symlink_metadata(&path)?;
+        if metadata.uid() != run_as.uid || metadata.gid() != run_as.gid {
+            lchown(&path, Some(run_as.uid), Some(run_as.gid))?;
+            changed += 1;
+        }
+        if metadata.is_dir() {
+            for entry in fs::read_dir(&path)? {
+                pending.push(entry?.path());
+            }
+        }
+    }
+    Ok(changed)
+}
+
+/// Finds the home directory of `uid` in the contents of /etc/passwd
+fn passwd_home(passwd: &str, uid: u32) -> Option<String> {
+    passwd.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        match fields.as_slice() {
+            [_, _, entry_uid, _, _, home, ..] if entry_uid.parse() == Ok(uid) => {
+                Some(home.to_string())
+            }
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passwd_home() {
+        let passwd = "root:x:0:0:root:/root:/bin/sh\n\
+                      node:x:1000:1000:Linux User,,,:/home/node:/bin/bash\n";
+        assert_eq!(passwd_home(passwd, 1000).as_deref(), Some("/home/node"));
+        assert_eq!(passwd_home(passwd, 0).as_deref(), Some("/root"));
+        assert_eq!(passwd_home(passwd, 1001), None);
+    }
+}
@@ -0,0 +1,196 @@
+use anyhow::{Context, Result, anyhow};
+use serde_json::{Value, json};
+use sha2::Sha512;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use tracing::{debug, info, warn};
+
+use crate::options;
+use crate::utils::files;
+
+/// Variables holding secrets in plain text, removed from the environment of Foundry and hooks
+pub const SECRET_VARS: [&str; 4] = [
+    "ADMIN_KEY",
+    "FOUNDRY_LICENSE_KEY",
+    "AUTH_TOKEN",
+    "AUTH_PASSWORD",
+];
+
+/// Foundry hashes the admin key with PBKDF2-HMAC-SHA512 and stores it as hex
+const PASSWORD_ITERATIONS: u32 = 1000;
+const PASSWORD_KEY_LENGTH: usize = 64;
+
+/// Number of license.json backups kept next to the file
+const LICENSE_BACKUPS: usize = 3;
+
+/// A value that must never end up in logs
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: String) -> Self {
+        Self(value)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+/// Reads a secret from the file named by `<name>_FILE`, or from `name` itself
+pub fn read_secret(name: &str) -> Result<Option<Secret>> {
+    let file_var = format!("{}_FILE", name);
+    let value = match env::var(&file_var) {
+        Ok(path) => {
+            if env::var(name).is_ok() {
+                warn!(
+                    "⚠️ Both {} and {} are set, using {}",
+                    name, file_var, file_var
+                );
+            }
+            fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {} from {}", name, path))?
+        }
+        Err(_) => match env::var(name) {
+            Ok(value) => value,
+            Err(_) => return Ok(None),
+        },
+    };
+
+    let value = value.trim();
+    Ok((!value.is_empty()).then(|| Secret(value.to_string())))
+}
+
+/// Writes `Config/admin.txt` so the setup screen asks for `key`.
+///
+/// The key is hashed with the `passwordSalt` from options.json, which is generated
+/// when the file does not have one yet.
+pub fn provision_admin_key(data_dir: &str, key: &Secret) -> Result<()> {
+    let options_path = options::options_json_path(data_dir);
+    let mut options = options::read_options_json(&options_path)?;
+    let salt = match options
+        .get("passwordSalt")
+        .and_then(Value::as_str)
+        .filter(|salt| !salt.is_empty())
+    {
+        Some(salt) => salt.to_string(),
+        None => {
+            let salt = random_hex(16)?;
+            options.insert("passwordSalt".to_string(), json!(salt));
+            options::write_options_json(&options_path, &options)?;
+            info!("Generated a password salt in {}", options_path.display());
+            salt
+        }
+    };
+
+    let hash = hash_password(key.expose(), &salt);
+    let admin_path = Path::new(data_dir).join("Config").join("admin.txt");
+    if fs::read_to_string(&admin_path).is_ok_and(|current| current.trim() == hash) {
+        debug!("{} is up to date", admin_path.display());
+        return Ok(());
+    }
+
+    files::write_atomic(&admin_path, &hash)?;
+    info!("🔐 Admin key provisioned in {}", admin_path.display());
+    Ok(())
+}
+
+/// Writes `Config/license.json` unless it already holds the same license key.
+/// Foundry signs the license on its first start.
+pub fn provision_license(data_dir: &str, key: &Secret) -> Result<()> {
+    let license = normalize_license_key(key.expose())
+        .ok_or_else(|| anyhow!("FOUNDRY_LICENSE_KEY is not a 24 character license key"))?;
+
+    let license_path = Path::new(data_dir).join("Config").join("license.json");
+    let current = fs::read_to_string(&license_path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Value>(&content).ok());
+    if current
+        .as_ref()
+        .and_then(|current| current.get("license"))
+        .and_then(Value::as_str)
+        == Some(license.as_str())
+    {
+        debug!("{} is up to date", license_path.display());
+        return Ok(());
+    }
+
+    let content = serde_json::to_string_pretty(&json!({ "license": license }))?;
+    files::write_with_backup(&license_path, &content, LICENSE_BACKUPS)?;
+    info!("🔑 License key provisioned in {}", license_path.display());
+    Ok(())
+}
+
+/// Hashes a password the way Foundry stores it
+fn hash_password(password: &str, salt: &str) -> String {
+    let mut key = [0u8; PASSWORD_KEY_LENGTH];
+    pbkdf2::pbkdf2_hmac::<Sha512>(
+        password.as_bytes(),
+        salt.as_bytes(),
+        PASSWORD_ITERATIONS,
+        &mut key,
+    );
+    to_hex(&key)
+}
+
+/// `len` random bytes from the kernel, hex encoded
+pub fn random_hex(len: usize) -> Result<String> {
+    let mut bytes = vec![0u8; len];
+    fs::File::open("/dev/urandom")
+        .and_then(|mut urandom| urandom.read_exact(&mut bytes))
+        .context("Failed to read random bytes")?;
+    Ok(to_hex(&bytes))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Removes dashes and whitespace from a license key and checks its length
+fn normalize_license_key(key: &str) -> Option<String> {
+    let key: String = key
+        .chars()
+        .filter(|c| *c != '-' && !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase();
+    (key.len() == 24 && key.chars().all(|c| c.is_ascii_alphanumeric())).then_some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_password_matches_node() {
+        // crypto.pbkdf2Sync("correct horse", "0f3d8c1a9b2e4f67", 1000, 64, "sha512").toString("hex")
+        assert_eq!(
+            hash_password("correct horse", "0f3d8c1a9b2e4f67"),
+            "da1842a52cfdf7ad6f0026020ac9d3a74e3b4934c65e77133851d39f6260f05a\
+             efa611a4691851376bef33c4a0b73cea247d6e9622b6e2288dbaf40fab944618"
+        );
+    }
+
+    #[test]
+    fn test_normalize_license_key() {
+        assert_eq!(
+            normalize_license_key("abcd-1234-EFGH-5678-ijkl-9012\n").as_deref(),
+            Some("ABCD1234EFGH5678IJKL9012")
+        );
+        assert_eq!(normalize_license_key("ABCD-1234"), None);
+        assert_eq!(normalize_license_key("ABCD-1234-EFGH-5678-IJKL-901!"), None);
+    }
+
+    #[test]
+    fn test_secret_debug_hides_value() {
+        let secret = Secret("hunter2".to_string());
+        assert_eq!(format!("{:?}", secret), "Secret(***)");
+    }
+}
@@ -0,0 +1,316 @@
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use rustls::ServerConfig;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{Duration, sleep};
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, warn};
+
+use crate::ports;
+
+type ProxyBody = BoxBody<Bytes, hyper::Error>;
+
+/// Headers that describe a single connection and must not be forwarded
+const HOP_BY_HOP: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "upgrade",
+];
+
+/// Loopback ports the public port forwards to
+#[derive(Debug, Clone, Copy)]
+pub struct Upstreams {
+    /// Foundry's internal port
+    pub foundry: u16,
+    /// The watcher's own pages, used whenever Foundry does not accept connections
+    pub fallback: u16,
+}
+
+/// Listens on the public port and forwards every request, WebSocket upgrades included,
+/// to Foundry or, while Foundry is down or not installed, to the watcher's own pages.
+/// With `tls`, clients connect over HTTPS and the upstreams still see plain HTTP.
+pub async fn start_proxy(
+    host: &str,
+    port: u16,
+    upstreams: Upstreams,
+    tls: Option<Arc<ServerConfig>>,
+) -> std::io::Result<()> {
+    let listener = TcpListener::bind((host, port))
+        .await
+        .map_err(|e| ports::bind_error(port, e))?;
+    let acceptor = tls.map(TlsAcceptor::from);
+    info!(
+        "🔀 Reverse proxy is running on {}://{}:{}, forwarding to FoundryVTT on port {}",
+        if acceptor.is_some() { "https" } else { "http" },
+        host,
+        port,
+        upstreams.foundry
+    );
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    // Usually out of file descriptors, give it a moment
+                    warn!("⚠️ Reverse proxy failed to accept a connection: {}", e);
+                    sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                match acceptor {
+                    Some(acceptor) => match acceptor.accept(stream).await {
+                        Ok(stream) => serve(stream, peer, upstreams, true).await,
+                        Err(e) => debug!("TLS handshake with {} failed: {}", peer, e),
+                    },
+                    None => serve(stream, peer, upstreams, false).await,
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+async fn serve<S>(stream: S, peer: SocketAddr, upstreams: Upstreams, https: bool)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = service_fn(move |req| forward(req, peer, upstreams, https));
+    if let Err(e) = http1::Builder::new()
+        .serve_connection(TokioIo::new(stream), service)
+        .with_upgrades()
+        .await
+    {
+        debug!("Proxy connection from {} ended: {}", peer, e);
+    }
+}
+
+async fn forward(
+    mut req: Request<Incoming>,
+    peer: SocketAddr,
+    upstreams: Upstreams,
+    https: bool,
+) -> Result<Response<ProxyBody>, hyper::Error> {
+    let stream = match TcpStream::connect(("127.0.0.1", upstreams.foundry)).await {
+        Ok(stream) => stream,
+        Err(_) => match TcpStream::connect(("127.0.0.1", upstreams.fallback)).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!(
+                    "⚠️ Neither FoundryVTT nor the watcher UI is reachable: {}",
+                    e
+                );
+                return Ok(text_response(
+                    StatusCode::BAD_GATEWAY,
+                    "Foundry VTT is not reachable",
+                ));
+            }
+        },
+    };
+    let _ = stream.set_nodelay(true);
+
+    let upgrade = is_upgrade(req.headers());
+    strip_hop_by_hop(req.headers_mut(), upgrade);
+    add_forwarded_headers(req.headers_mut(), peer, https);
+    let client_upgrade = upgrade.then(|| hyper::upgrade::on(&mut req));
+
+    let (mut sender, connection) =
+        match hyper::client::conn::http1::handshake(TokioIo::new(stream)).await {
+            Ok(handshake) => handshake,
+            Err(e) => {
+                warn!("⚠️ Reverse proxy handshake with upstream failed: {}", e);
+                return Ok(text_response(
+                    StatusCode::BAD_GATEWAY,
+                    "Foundry VTT is not reachable",
+                ));
+            }
+        };
+    tokio::spawn(async move {
+        if let Err(e) = connection.with_upgrades().await {
+            debug!("Upstream connection ended: {}", e);
+        }
+    });
+
+    let mut response = match sender.send_request(req).await {
+        Ok(response) => response,
+        Err(e) => {
+            debug!("Upstream request failed: {}", e);
+            return Ok(text_response(
+                StatusCode::BAD_GATEWAY,
+                "Foundry VTT closed the connection",
+            ));
+        }
+    };
+
+    if response.status() == StatusCode::SWITCHING_PROTOCOLS
+        && let Some(client_upgrade) = client_upgrade
+    {
+        let upstream_upgrade = hyper::upgrade::on(&mut response);
+        tokio::spawn(async move {
+            match tokio::try_join!(client_upgrade, upstream_upgrade) {
+                Ok((client, upstream)) => {
+                    let mut client = TokioIo::new(client);
+                    let mut upstream = TokioIo::new(upstream);
+                    let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+                }
+                Err(e) => debug!("WebSocket upgrade from {} failed: {}", peer, e),
+            }
+        });
+    } else {
+        strip_hop_by_hop(response.headers_mut(), false);
+    }
+
+    Ok(response.map(|body| body.boxed()))
+}
+
+/// True for requests asking to switch protocols, e.g. Socket.IO's WebSocket transport
+fn is_upgrade(headers: &HeaderMap) -> bool {
+    headers.contains_key(header::UPGRADE)
+        && headers
+            .get_all(header::CONNECTION)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+}
+
+/// Removes per-connection headers. An upgrade keeps `Connection` and `Upgrade`,
+/// which the upstream needs to see to switch protocols.
+fn strip_hop_by_hop(headers: &mut HeaderMap, upgrade: bool) {
+    // Headers named in Connection are hop-by-hop as well
+    let named: Vec<HeaderName> = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|token| HeaderName::from_bytes(token.trim().as_bytes()).ok())
+        .filter(|name| name != header::UPGRADE)
+        .collect();
+    for name in named {
+        headers.remove(name);
+    }
+
+    for name in HOP_BY_HOP {
+        if upgrade && (name == "connection" || name == "upgrade") {
+            continue;
+        }
+        headers.remove(name);
+    }
+}
+
+/// Tells the upstream who the client is, appending to headers set by proxies further out
+fn add_forwarded_headers(headers: &mut HeaderMap, peer: SocketAddr, https: bool) {
+    let client = peer.ip().to_string();
+    let forwarded_for = match headers
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(existing) => format!("{}, {}", existing, client),
+        None => client,
+    };
+    if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+        headers.insert("x-forwarded-for", value);
+    }
+
+    if https {
+        headers.insert("x-forwarded-proto", HeaderValue::from_static("https"));
+    } else if !headers.contains_key("x-forwarded-proto") {
+        headers.insert("x-forwarded-proto", HeaderValue::from_static("http"));
+    }
+    if !headers.contains_key("x-forwarded-host")
+        && let Some(host) = headers.get(header::HOST).cloned()
+    {
+        headers.insert("x-forwarded-host", host);
+    }
+}
+
+fn text_response(status: StatusCode, message: &'static str) -> Response<ProxyBody> {
+    let mut response = Response::new(
+        Full::new(Bytes::from_static(message.as_bytes()))
+            .map_err(|never| match never {})
+            .boxed(),
+    );
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn test_websocket_upgrade_keeps_upgrade_headers() {
+        let mut headers = headers(&[
+            ("connection", "keep-alive, Upgrade"),
+            ("upgrade", "websocket"),
+            ("keep-alive", "timeout=5"),
+            ("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ=="),
+        ]);
+        assert!(is_upgrade(&headers));
+        strip_hop_by_hop(&mut headers, true);
+        assert!(headers.contains_key("upgrade"));
+        assert!(headers.contains_key("connection"));
+        assert!(headers.contains_key("sec-websocket-key"));
+        assert!(!headers.contains_key("keep-alive"));
+    }
+
+    #[test]
+    fn test_plain_request_drops_hop_by_hop_headers() {
+        let mut headers = headers(&[
+            ("connection", "close, x-debug"),
+            ("x-debug", "1"),
+            ("accept", "text/html"),
+        ]);
+        assert!(!is_upgrade(&headers));
+        strip_hop_by_hop(&mut headers, false);
+        assert!(!headers.contains_key("connection"));
+        assert!(!headers.contains_key("x-debug"));
+        assert!(headers.contains_key("accept"));
+    }
+
+    #[test]
+    fn test_forwarded_for_is_appended() {
+        let mut headers = headers(&[("x-forwarded-for", "203.0.113.7"), ("host", "vtt.example")]);
+        add_forwarded_headers(&mut headers, "10.0.0.2:51234".parse().unwrap(), false);
+        assert_eq!(headers["x-forwarded-for"], "203.0.113.7, 10.0.0.2");
+        assert_eq!(headers["x-forwarded-host"], "vtt.example");
+        assert_eq!(headers["x-forwarded-proto"], "http");
+    }
+
+    #[test]
+    fn test_tls_overrides_forwarded_proto() {
+        let mut headers = headers(&[("x-forwarded-proto", "http")]);
+        add_forwarded_headers(&mut headers, "10.0.0.2:51234".parse().unwrap(), true);
+        assert_eq!(headers["x-forwarded-proto"], "https");
+    }
+}
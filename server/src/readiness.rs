@@ -0,0 +1,275 @@
+use actix_web::{HttpResponse, Responder, web};
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, interval, timeout};
+use tracing::{debug, info, warn};
+
+use crate::events::ProgressEvent;
+use crate::server::AppState;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Lifecycle of a spawned Foundry process as seen from the outside
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadinessState {
+    /// No Foundry process is running
+    Stopped,
+    /// The process was spawned but its port is not accepting connections yet
+    Starting,
+    /// The port accepts connections but `/api/status` does not answer yet
+    Listening,
+    /// `/api/status` answers with a valid document
+    Ready,
+    /// Foundry was listening or ready before and stopped answering
+    Degraded,
+}
+
+impl ReadinessState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReadinessState::Stopped => "stopped",
+            ReadinessState::Starting => "starting",
+            ReadinessState::Listening => "listening",
+            ReadinessState::Ready => "ready",
+            ReadinessState::Degraded => "degraded",
+        }
+    }
+}
+
+/// Document returned by Foundry's `/api/status` endpoint
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FoundryServerStatus {
+    #[serde(default)]
+    pub active: bool,
+    pub version: Option<String>,
+    pub world: Option<String>,
+    pub system: Option<String>,
+    pub system_version: Option<String>,
+    pub users: Option<u32>,
+    pub uptime: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadinessSnapshot {
+    pub state: ReadinessState,
+    pub since: DateTime<Utc>,
+    pub foundry: Option<FoundryServerStatus>,
+}
+
+/// Current readiness of Foundry, shared between the probe, the supervisor and the HTTP server
+pub struct Readiness {
+    inner: Mutex<ReadinessSnapshot>,
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self {
+            inner: Mutex::new(ReadinessSnapshot {
+                state: ReadinessState::Stopped,
+                since: Utc::now(),
+                foundry: None,
+            }),
+        }
+    }
+}
+
+impl Readiness {
+    pub fn snapshot(&self) -> ReadinessSnapshot {
+        self.inner.lock().unwrap().clone()
+    }
+
+    pub fn state(&self) -> ReadinessState {
+        self.inner.lock().unwrap().state
+    }
+
+    /// Records the latest probe result and publishes a `readiness` event when the state changes
+    pub fn update(
+        &self,
+        state: ReadinessState,
+        foundry: Option<FoundryServerStatus>,
+        event_tx: &broadcast::Sender<ProgressEvent>,
+    ) {
+        let mut inner = self.inner.lock().unwrap();
+        // Keep the last known Foundry status until the process goes away
+        if foundry.is_some() || state == ReadinessState::Stopped {
+            inner.foundry = foundry;
+        }
+        if inner.state == state {
+            return;
+        }
+
+        let previous = inner.state;
+        inner.state = state;
+        inner.since = Utc::now();
+        drop(inner);
+
+        let message = format!("FoundryVTT is {}", state.as_str());
+        if state == ReadinessState::Degraded {
+            warn!("{} (was {})", message, previous.as_str());
+        } else {
+            info!("{} (was {})", message, previous.as_str());
+        }
+        let _ = event_tx.send(ProgressEvent::new("readiness", &message, None));
+    }
+}
+
+/// Works out the next state from the previous one and the outcome of a probe
+fn next_state(
+    previous: ReadinessState,
+    listening: bool,
+    status: Option<&FoundryServerStatus>,
+) -> ReadinessState {
+    match (listening, status.is_some()) {
+        (true, true) => ReadinessState::Ready,
+        (true, false) => match previous {
+            ReadinessState::Ready | ReadinessState::Degraded => ReadinessState::Degraded,
+            _ => ReadinessState::Listening,
+        },
+        (false, _) => match previous {
+            ReadinessState::Stopped | ReadinessState::Starting => ReadinessState::Starting,
+            _ => ReadinessState::Degraded,
+        },
+    }
+}
+
+/// Returns true when something accepts TCP connections on `port`
+pub async fn port_accepts_connections(port: u16) -> bool {
+    matches!(
+        timeout(CONNECT_TIMEOUT, TcpStream::connect(("127.0.0.1", port))).await,
+        Ok(Ok(_))
+    )
+}
+
+/// Fetches and parses Foundry's `/api/status` document
+pub async fn fetch_status(client: &Client, url: &str) -> Option<FoundryServerStatus> {
+    let response = match client.get(url).timeout(REQUEST_TIMEOUT).send().await {
+        Ok(response) if response.status().is_success() => response,
+        Ok(response) => {
+            debug!("Status endpoint answered with {}", response.status());
+            return None;
+        }
+        Err(e) => {
+            debug!("Status request failed: {}", e);
+            return None;
+        }
+    };
+
+    match response.json::<FoundryServerStatus>().await {
+        Ok(status) => Some(status),
+        Err(e) => {
+            debug!("Status endpoint returned an unexpected document: {}", e);
+            None
+        }
+    }
+}
+
+/// HTTP client for probing Foundry on localhost. Certificates are not verified
+/// because Foundry may serve its own, typically self-signed, certificate.
+pub fn probe_client() -> Client {
+    Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap_or_default()
+}
+
+/// Polls Foundry on `port` and `status_url` every `period` until the returned task is aborted
+pub fn spawn_probe(
+    port: u16,
+    status_url: String,
+    period: Duration,
+    app_state: web::Data<AppState>,
+) -> JoinHandle<()> {
+    app_state
+        .readiness
+        .update(ReadinessState::Starting, None, &app_state.event_channel);
+
+    tokio::spawn(async move {
+        let client = probe_client();
+        let mut ticker = interval(period);
+
+        loop {
+            ticker.tick().await;
+
+            let listening = port_accepts_connections(port).await;
+            let status = if listening {
+                fetch_status(&client, &status_url).await
+            } else {
+                None
+            };
+
+            let state = next_state(app_state.readiness.state(), listening, status.as_ref());
+            app_state
+                .readiness
+                .update(state, status, &app_state.event_channel);
+        }
+    })
+}
+
+pub async fn healthz() -> impl Responder {
+    // The watcher itself is alive if it can answer at all
+    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
+}
+
+pub async fn readyz(app_state: web::Data<AppState>) -> impl Responder {
+    let snapshot = app_state.readiness.snapshot();
+    if snapshot.state == ReadinessState::Ready {
+        HttpResponse::Ok().json(snapshot)
+    } else {
+        HttpResponse::ServiceUnavailable().json(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_state_startup_sequence() {
+        let status = FoundryServerStatus::default();
+        let state = next_state(ReadinessState::Stopped, false, None);
+        assert_eq!(state, ReadinessState::Starting);
+        let state = next_state(state, true, None);
+        assert_eq!(state, ReadinessState::Listening);
+        let state = next_state(state, true, Some(&status));
+        assert_eq!(state, ReadinessState::Ready);
+    }
+
+    #[test]
+    fn test_next_state_degrades_and_recovers() {
+        let status = FoundryServerStatus::default();
+        assert_eq!(
+            next_state(ReadinessState::Ready, true, None),
+            ReadinessState::Degraded
+        );
+        assert_eq!(
+            next_state(ReadinessState::Ready, false, None),
+            ReadinessState::Degraded
+        );
+        assert_eq!(
+            next_state(ReadinessState::Listening, false, None),
+            ReadinessState::Degraded
+        );
+        assert_eq!(
+            next_state(ReadinessState::Degraded, true, Some(&status)),
+            ReadinessState::Ready
+        );
+    }
+
+    #[test]
+    fn test_parse_foundry_status() {
+        let json = r#"{"active":true,"version":"12.331","world":"campaign","system":"dnd5e","systemVersion":"4.1.2","users":3,"uptime":120.5}"#;
+        let status: FoundryServerStatus = serde_json::from_str(json).unwrap();
+        assert!(status.active);
+        assert_eq!(status.version.as_deref(), Some("12.331"));
+        assert_eq!(status.world.as_deref(), Some("campaign"));
+        assert_eq!(status.users, Some(3));
+    }
+}
@@ -0,0 +1,279 @@
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+use std::fs;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicI32, Ordering};
+use tokio::time::{Duration, Instant, sleep};
+use tracing::{debug, error, info, warn};
+
+/// How long a terminating signal waits for the Foundry process group to exit
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// Process group of the running Foundry child, or 0 when nothing is running
+static FOUNDRY_PGID: AtomicI32 = AtomicI32::new(0);
+
+lazy_static! {
+    /// Children whose exit status is collected by their owner (e.g. the tokio `Child` handle)
+    static ref MANAGED_PIDS: Mutex<HashSet<i32>> = Mutex::new(HashSet::new());
+}
+
+/// Returns true when the watcher is the container's init process
+pub fn is_pid1() -> bool {
+    std::process::id() == 1
+}
+
+/// Keeps the reaper away from a child started in its own process group
+/// whose exit status is collected by its owner
+pub fn manage_child(pid: u32) {
+    MANAGED_PIDS.lock().unwrap().insert(pid as i32);
+}
+
+/// Forgets a managed child once its exit status has been collected
+pub fn release_child(pid: u32) {
+    MANAGED_PIDS.lock().unwrap().remove(&(pid as i32));
+}
+
+/// Records a freshly spawned Foundry child. The child must have been started
+/// in its own process group so that its PID doubles as the group ID.
+pub fn register_foundry(pid: u32) {
+    manage_child(pid);
+    FOUNDRY_PGID.store(pid as i32, Ordering::SeqCst);
+    debug!("Tracking FoundryVTT process group {}", pid);
+}
+
+/// Forgets a Foundry child once its exit status has been collected
+pub fn unregister_foundry(pid: u32) {
+    release_child(pid);
+    let pid = pid as i32;
+    let _ = FOUNDRY_PGID.compare_exchange(pid, 0, Ordering::SeqCst, Ordering::SeqCst);
+}
+
+/// Sends `signal` to every process in the Foundry process group.
+/// Returns false when there is no running group to signal.
+pub fn signal_foundry_group(signal: i32) -> bool {
+    let pgid = FOUNDRY_PGID.load(Ordering::SeqCst);
+    if pgid <= 0 {
+        return false;
+    }
+
+    // SAFETY: kill(2) with a negative PID signals the whole process group
+    if unsafe { libc::kill(-pgid, signal) } != 0 {
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::ESRCH) {
+            error!(
+                "Failed to send signal {} to FoundryVTT group {}: {}",
+                signal, pgid, err
+            );
+        }
+        return false;
+    }
+
+    debug!("Forwarded signal {} to FoundryVTT group {}", signal, pgid);
+    true
+}
+
+/// Returns true while any process of the Foundry group is still alive
+fn foundry_group_alive() -> bool {
+    let pgid = FOUNDRY_PGID.load(Ordering::SeqCst);
+    // SAFETY: signal 0 only performs the existence and permission checks
+    pgid > 0 && unsafe { libc::kill(-pgid, 0) } == 0
+}
+
+/// Starts reaping orphaned processes on SIGCHLD when running as PID 1
+pub fn spawn_reaper() {
+    if !is_pid1() {
+        debug!("Not running as PID 1, zombie reaping disabled");
+        return;
+    }
+
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        info!("Running as PID 1, reaping orphaned processes");
+        tokio::spawn(async move {
+            let mut sigchld = match signal(SignalKind::child()) {
+                Ok(sigchld) => sigchld,
+                Err(e) => {
+                    error!("Failed to listen for SIGCHLD: {}", e);
+                    return;
+                }
+            };
+
+            // Catch anything that exited before the handler was installed
+            reap_orphans();
+            while sigchld.recv().await.is_some() {
+                reap_orphans();
+            }
+        });
+    }
+}
+
+/// Collects zombies that were re-parented to us.
+///
+/// Processes sharing our own process group are children we spawned ourselves
+/// (e.g. `run_command`), and their owners wait on them. Everything else that
+/// lingers as a zombie under us is an orphan from the Foundry tree.
+fn reap_orphans() {
+    let own_pid = std::process::id() as i32;
+    // SAFETY: getpgrp(2) cannot fail
+    let own_pgid = unsafe { libc::getpgrp() };
+
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Failed to scan /proc for zombies: {}", e);
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<i32>().ok())
+        else {
+            continue;
+        };
+
+        let Ok(stat) = fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        let Some(proc_stat) = parse_proc_stat(&stat) else {
+            continue;
+        };
+
+        if proc_stat.state != 'Z'
+            || proc_stat.ppid != own_pid
+            || proc_stat.pgrp == own_pgid
+            || MANAGED_PIDS.lock().unwrap().contains(&pid)
+        {
+            continue;
+        }
+
+        let mut status = 0;
+        // SAFETY: waitpid(2) on a known zombie child with WNOHANG never blocks
+        let reaped = unsafe { libc::waitpid(pid, &mut status, libc::WNOHANG) };
+        if reaped == pid {
+            debug!("Reaped orphaned process {} (status {})", pid, status);
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct ProcStat {
+    state: char,
+    ppid: i32,
+    pgrp: i32,
+}
+
+/// Parses the state, parent PID and process group from `/proc/<pid>/stat`
+fn parse_proc_stat(stat: &str) -> Option<ProcStat> {
+    // The command name is wrapped in parentheses and may itself contain spaces
+    // or parentheses, so the fixed fields start after the last ')'
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let mut fields = rest.split_whitespace();
+
+    let state = fields.next()?.chars().next()?;
+    let ppid = fields.next()?.parse().ok()?;
+    let pgrp = fields.next()?.parse().ok()?;
+
+    Some(ProcStat { state, ppid, pgrp })
+}
+
+/// Installs handlers that forward signals to the Foundry process group.
+/// Terminating signals give Foundry a grace period before the watcher exits.
+pub fn setup_signal_handlers() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let signals = [
+            (SignalKind::terminate(), libc::SIGTERM, "SIGTERM", true),
+            (SignalKind::interrupt(), libc::SIGINT, "SIGINT", true),
+            (SignalKind::quit(), libc::SIGQUIT, "SIGQUIT", true),
+            (SignalKind::hangup(), libc::SIGHUP, "SIGHUP", false),
+            (SignalKind::user_defined1(), libc::SIGUSR1, "SIGUSR1", false),
+            (SignalKind::user_defined2(), libc::SIGUSR2, "SIGUSR2", false),
+        ];
+
+        for (kind, signum, name, terminates) in signals {
+            let mut stream = match signal(kind) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    error!("Failed to install {} handler: {}", name, e);
+                    continue;
+                }
+            };
+
+            tokio::spawn(async move {
+                while stream.recv().await.is_some() {
+                    info!("Received {}", name);
+                    let forwarded = signal_foundry_group(signum);
+
+                    if terminates {
+                        if forwarded {
+                            wait_for_foundry_exit().await;
+                        }
+                        info!("Initiating shutdown");
+                        std::process::exit(0);
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// Waits for the Foundry process group to exit, escalating to SIGKILL after the grace period
+async fn wait_for_foundry_exit() {
+    let deadline = Instant::now() + SHUTDOWN_GRACE_PERIOD;
+    while foundry_group_alive() {
+        if Instant::now() >= deadline {
+            warn!(
+                "FoundryVTT did not exit within {}s, sending SIGKILL",
+                SHUTDOWN_GRACE_PERIOD.as_secs()
+            );
+            signal_foundry_group(libc::SIGKILL);
+            return;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    info!("FoundryVTT process group exited");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_stat() {
+        let stat = "42 (node) Z 1 40 40 0 -1 4194564 0 0 0 0 0 0 0 0 20 0 1 0";
+        assert_eq!(
+            parse_proc_stat(stat),
+            Some(ProcStat {
+                state: 'Z',
+                ppid: 1,
+                pgrp: 40
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_proc_stat_command_with_parentheses() {
+        let stat = "7 (weird) name (x)) S 3 7 7 0 -1";
+        assert_eq!(
+            parse_proc_stat(stat),
+            Some(ProcStat {
+                state: 'S',
+                ppid: 3,
+                pgrp: 7
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_proc_stat_truncated() {
+        assert_eq!(parse_proc_stat("7 (node) S"), None);
+        assert_eq!(parse_proc_stat("garbage"), None);
+    }
+}
@@ -0,0 +1,295 @@
+use chrono::{DateTime, Duration, Local, Utc};
+use cron::Schedule;
+use std::str::FromStr;
+
+const WEEKDAYS: [&str; 8] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Parses a cron expression in the classic five-field form (`min hour dom month dow`),
+/// or the six/seven-field form with seconds understood by the `cron` crate.
+///
+/// Numeric weekdays in five-field expressions follow crontab(5) (0 or 7 = Sunday)
+/// and are translated to names, because the `cron` crate counts Sunday as 1.
+pub fn parse_cron(expr: &str) -> Result<Schedule, String> {
+    let fields: Vec<&str> = expr.split_whitespace().collect();
+    let normalized = match fields.len() {
+        5 => format!(
+            "0 {} {} {} {} {}",
+            fields[0],
+            fields[1],
+            fields[2],
+            fields[3],
+            crontab_weekdays(fields[4])
+        ),
+        6 | 7 => fields.join(" "),
+        n => {
+            return Err(format!(
+                "expected 5 fields (min hour dom month dow), got {}",
+                n
+            ));
+        }
+    };
+
+    Schedule::from_str(&normalized).map_err(|e| e.to_string())
+}
+
+/// Replaces crontab(5) weekday numbers with names, leaving step values alone
+fn crontab_weekdays(field: &str) -> String {
+    field
+        .split(',')
+        .map(|item| range_to_sunday(item).unwrap_or_else(|| weekday_names(item)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Lists the days of a numeric range ending in 7, e.g. `5-7` as `Fri,Sat,Sun`,
+/// because the `cron` crate rejects a named range such as `Fri-Sun` that wraps
+/// past Saturday
+fn range_to_sunday(item: &str) -> Option<String> {
+    let (range, step) = match item.split_once('/') {
+        Some((range, step)) => (range, step.parse::<usize>().ok().filter(|s| *s > 0)?),
+        None => (item, 1),
+    };
+    let (first, last) = range.split_once('-')?;
+    let first = first.parse::<usize>().ok()?;
+    if last != "7" || first > 7 {
+        return None;
+    }
+    let days: Vec<_> = (first..=7).step_by(step).map(|day| WEEKDAYS[day]).collect();
+    Some(days.join(","))
+}
+
+fn weekday_names(item: &str) -> String {
+    let mut out = String::with_capacity(item.len());
+    let mut chars = item.chars().peekable();
+    let mut after_step = false;
+
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            let mut number = c.to_string();
+            while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                number.push(*d);
+                chars.next();
+            }
+            match number.parse::<usize>().ok().filter(|_| !after_step) {
+                Some(day) if day < WEEKDAYS.len() => out.push_str(WEEKDAYS[day]),
+                _ => out.push_str(&number),
+            }
+            after_step = false;
+        } else {
+            after_step = c == '/';
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Parses durations such as `90m`, `6h`, `1d` or a plain number of minutes
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = value
+        .find(|c: char| !c.is_ascii_digit())
+        .map(|i| value.split_at(i))
+        .unwrap_or((value, "m"));
+    let amount: i64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration: {}", value))?;
+
+    match unit.trim() {
+        "s" => Ok(Duration::seconds(amount)),
+        "m" => Ok(Duration::minutes(amount)),
+        "h" => Ok(Duration::hours(amount)),
+        "d" => Ok(Duration::days(amount)),
+        other => Err(format!("unknown duration unit '{}' in {}", other, value)),
+    }
+    .and_then(|duration| {
+        if duration > Duration::zero() {
+            Ok(duration)
+        } else {
+            Err(format!("duration must be positive: {}", value))
+        }
+    })
+}
+
+/// Recurring period during which Foundry is allowed to run
+#[derive(Debug, Clone)]
+pub struct PlayWindow {
+    pub start: Schedule,
+    pub duration: Duration,
+}
+
+impl PlayWindow {
+    /// Returns when the window that contains `now` closes, or None when it is closed
+    pub fn closes_at(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        // The window is open if one of its starts lies within the last `duration`
+        let earliest = now - self.duration;
+        self.start
+            .after(&earliest)
+            .take_while(|start| *start <= now)
+            .last()
+            .map(|start| start + self.duration)
+    }
+
+    /// Returns when the window opens next, assuming it is closed at `now`
+    pub fn opens_at(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        self.start.after(&now).next()
+    }
+}
+
+/// Schedules that control when the supervisor restarts or stops Foundry
+#[derive(Debug, Clone, Default)]
+pub struct Schedules {
+    pub restart: Option<Schedule>,
+    pub play_window: Option<PlayWindow>,
+}
+
+impl Schedules {
+    /// Next scheduled maintenance restart after `now`
+    pub fn next_restart(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        self.restart.as_ref()?.after(&now).next()
+    }
+
+    /// Returns None when Foundry may run at `now`, otherwise when it may start again.
+    /// The inner value is None when the window never opens again.
+    pub fn scheduled_off(&self, now: DateTime<Local>) -> Option<Option<DateTime<Local>>> {
+        let window = self.play_window.as_ref()?;
+        if window.closes_at(now).is_some() {
+            return None;
+        }
+        Some(window.opens_at(now))
+    }
+
+    /// Earliest point after `now` at which a running Foundry must be stopped,
+    /// together with whether it should come back immediately (a restart)
+    pub fn next_stop(&self, now: DateTime<Local>) -> Option<(DateTime<Local>, ScheduledStop)> {
+        let restart = self
+            .next_restart(now)
+            .map(|at| (at, ScheduledStop::Restart));
+        let close = self
+            .play_window
+            .as_ref()
+            .and_then(|window| window.closes_at(now))
+            .map(|at| (at, ScheduledStop::WindowClosed));
+
+        match (restart, close) {
+            (Some(restart), Some(close)) => {
+                Some(if close.0 <= restart.0 { close } else { restart })
+            }
+            (restart, close) => restart.or(close),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScheduledStop {
+    Restart,
+    WindowClosed,
+}
+
+/// Converts a local schedule time into the UTC form used by status documents
+pub fn to_utc(at: DateTime<Local>) -> DateTime<Utc> {
+    at.with_timezone(&Utc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone};
+
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        Local
+            .from_local_datetime(
+                &NaiveDate::from_ymd_opt(y, m, d)
+                    .unwrap()
+                    .and_hms_opt(h, min, 0)
+                    .unwrap(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_crontab_weekdays() {
+        assert_eq!(crontab_weekdays("2,5"), "Tue,Fri");
+        assert_eq!(crontab_weekdays("1-5"), "Mon-Fri");
+        assert_eq!(crontab_weekdays("0"), "Sun");
+        assert_eq!(crontab_weekdays("7"), "Sun");
+        assert_eq!(crontab_weekdays("*/2"), "*/2");
+        assert_eq!(crontab_weekdays("Tue,Fri"), "Tue,Fri");
+    }
+
+    #[test]
+    fn test_parse_cron_five_fields() {
+        let schedule = parse_cron("0 5 * * *").unwrap();
+        // 2026-01-06 is a Tuesday
+        let next = schedule.after(&local(2026, 1, 6, 12, 0)).next().unwrap();
+        assert_eq!(next, local(2026, 1, 7, 5, 0));
+
+        assert!(parse_cron("0 5 * *").is_err());
+        assert!(parse_cron("61 5 * * *").is_err());
+    }
+
+    #[test]
+    fn test_parse_cron_weekday_range_ending_in_sunday() {
+        assert_eq!(crontab_weekdays("5-7"), "Fri,Sat,Sun");
+        assert_eq!(crontab_weekdays("1,4-7/2"), "Mon,Thu,Sat");
+
+        let schedule = parse_cron("0 18 * * 5-7").unwrap();
+        // From Tuesday 2026-01-06: Friday, Saturday and Sunday, then the next Friday
+        let runs: Vec<_> = schedule.after(&local(2026, 1, 6, 12, 0)).take(4).collect();
+        assert_eq!(
+            runs,
+            [
+                local(2026, 1, 9, 18, 0),
+                local(2026, 1, 10, 18, 0),
+                local(2026, 1, 11, 18, 0),
+                local(2026, 1, 16, 18, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90m"), Ok(Duration::minutes(90)));
+        assert_eq!(parse_duration("6h"), Ok(Duration::hours(6)));
+        assert_eq!(parse_duration("45"), Ok(Duration::minutes(45)));
+        assert!(parse_duration("0h").is_err());
+        assert!(parse_duration("6w").is_err());
+    }
+
+    #[test]
+    fn test_play_window() {
+        // Tuesdays and Fridays from 18:00 for six hours
+        let schedules = Schedules {
+            restart: None,
+            play_window: Some(PlayWindow {
+                start: parse_cron("0 18 * * 2,5").unwrap(),
+                duration: Duration::hours(6),
+            }),
+        };
+
+        // Tuesday 20:00 is inside the window, which closes at midnight
+        assert_eq!(schedules.scheduled_off(local(2026, 1, 6, 20, 0)), None);
+        assert_eq!(
+            schedules.next_stop(local(2026, 1, 6, 20, 0)),
+            Some((local(2026, 1, 7, 0, 0), ScheduledStop::WindowClosed))
+        );
+
+        // Wednesday noon is outside, Foundry comes back Friday 18:00
+        assert_eq!(
+            schedules.scheduled_off(local(2026, 1, 7, 12, 0)),
+            Some(Some(local(2026, 1, 9, 18, 0)))
+        );
+    }
+
+    #[test]
+    fn test_restart_without_play_window() {
+        let schedules = Schedules {
+            restart: Some(parse_cron("0 5 * * *").unwrap()),
+            play_window: None,
+        };
+        assert_eq!(schedules.scheduled_off(local(2026, 1, 6, 20, 0)), None);
+        assert_eq!(
+            schedules.next_stop(local(2026, 1, 6, 20, 0)),
+            Some((local(2026, 1, 7, 5, 0), ScheduledStop::Restart))
+        );
+    }
+}
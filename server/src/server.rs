@@ -1,13 +1,25 @@
+use crate::auth::{self, Auth};
 use crate::config::AppConfig;
 use crate::events::{self, ProgressEvent};
 use crate::handlers;
+use crate::jobs::InstallJobs;
+use crate::launch::{SharedSupervisorStatus, SupervisorCommand};
+use crate::logs::{self, LogBuffer, SharedLogBuffer};
+use crate::metrics::{self, Metrics};
+use crate::ports;
+use crate::readiness::{self, Readiness};
+use crate::status::{self, ConfigSummary};
+use crate::utils::paths;
 use actix_files::Files;
-use actix_web::dev::ServiceResponse;
-use actix_web::http::{StatusCode, header};
-use actix_web::middleware::{ErrorHandlerResponse, ErrorHandlers};
-use actix_web::{App, HttpResponse, HttpServer, Result, web};
+use actix_web::dev::ServerHandle;
+use actix_web::middleware::from_fn;
+use actix_web::{App, HttpServer, guard, web};
+use chrono::{DateTime, Utc};
+use rustls::ServerConfig;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
-use tokio::sync::{broadcast, oneshot};
+use tokio::sync::{Notify, broadcast, mpsc, oneshot};
 use tokio::task::JoinHandle;
 use tracing::{debug, info};
 use tracing_actix_web::TracingLogger;
@@ -15,101 +27,262 @@ use tracing_actix_web::TracingLogger;
 pub struct AppState {
     pub shutdown_sender: Arc<Mutex<Option<oneshot::Sender<()>>>>,
     pub event_channel: broadcast::Sender<ProgressEvent>,
+    pub supervisor: SharedSupervisorStatus,
+    pub logs: SharedLogBuffer,
+    pub readiness: Readiness,
+    /// Requests for the Foundry supervisor
+    pub commands: mpsc::Sender<SupervisorCommand>,
+    /// Taken by the supervisor when it starts
+    pub command_receiver: Mutex<Option<mpsc::Receiver<SupervisorCommand>>>,
+    /// Set from the moment an update is requested until it is installed
+    pub upgrading: AtomicBool,
+    pub backing_up: AtomicBool,
+    pub backup_dir: PathBuf,
+    pub backup_keep: usize,
+    /// Why the installed Foundry is considered broken, which brings the installer back
+    pub install_failure: Mutex<Option<String>>,
+    /// Notified when the installer replaced a broken installation
+    pub reinstalled: Notify,
+    pub auth: Auth,
+    pub metrics: Metrics,
+    /// The installation or upgrade in progress and the last finished ones
+    pub installs: InstallJobs,
+    pub started_at: DateTime<Utc>,
+    pub config_summary: ConfigSummary,
 }
 
-pub async fn start_server(config: &AppConfig) -> std::io::Result<JoinHandle<std::io::Result<()>>> {
-    // Create a channel for shutting down the server
-    let (tx, rx) = oneshot::channel::<()>();
+impl AppState {
+    /// Creates the state shared between the HTTP server and the Foundry supervisor
+    pub fn new(config: &AppConfig, auth: Auth) -> web::Data<Self> {
+        // Create a broadcast channel for SSE events
+        let (event_tx, _) = broadcast::channel::<ProgressEvent>(100);
+        let (command_tx, command_rx) = mpsc::channel::<SupervisorCommand>(8);
 
-    // Wrap the Sender in Arc<Mutex<Option<...>>> so it can be shared and taken
-    let shared_tx = Arc::new(Mutex::new(Some(tx)));
-
-    // Create a broadcast channel for SSE events
-    let (event_tx, _) = broadcast::channel::<ProgressEvent>(100);
-
-    let app_state = web::Data::new(AppState {
-        shutdown_sender: Arc::clone(&shared_tx),
-        event_channel: event_tx,
-    });
+        web::Data::new(Self {
+            shutdown_sender: Arc::new(Mutex::new(None)),
+            event_channel: event_tx,
+            supervisor: SharedSupervisorStatus::default(),
+            logs: Arc::new(LogBuffer::new(config.log_buffer_lines)),
+            readiness: Readiness::default(),
+            commands: command_tx,
+            command_receiver: Mutex::new(Some(command_rx)),
+            upgrading: AtomicBool::new(false),
+            backing_up: AtomicBool::new(false),
+            backup_dir: config.backup_dir.clone(),
+            backup_keep: config.backup_keep,
+            install_failure: Mutex::new(None),
+            reinstalled: Notify::new(),
+            auth,
+            metrics: Metrics::default(),
+            installs: InstallJobs::default(),
+            started_at: Utc::now(),
+            config_summary: ConfigSummary::new(config),
+        })
+    }
 
-    info!(
-        "Server is running on {}:{}",
-        config.server_host, config.server_port
-    );
-    debug!("Debug logging is enabled");
+    /// True while the installer accepts archives: before Foundry is installed,
+    /// and again once the installed copy turned out to be broken
+    pub fn installer_active(&self) -> bool {
+        !paths::FOUNDRY_SCRIPT_PATH.exists() || self.install_failure.lock().unwrap().is_some()
+    }
+}
 
+/// Serves the installer UI on `host:port`, where port 0 picks a free port.
+/// Returns the server's handle, the port it is bound to and the task running it.
+pub fn start_server(
+    config: &AppConfig,
+    app_state: web::Data<AppState>,
+    host: &str,
+    port: u16,
+) -> std::io::Result<(ServerHandle, u16, JoinHandle<std::io::Result<()>>)> {
     // Clone the values we need inside the closure to avoid lifetime issues
     let static_files_dir = config.static_files_dir.clone();
-    let server_host = config.server_host.clone();
-    let server_port = config.server_port;
+    let maintenance_page = format!("{}/maintenance.html", config.static_files_dir);
+    let behind_proxy = config.builtin_proxy;
 
     // Start the server
     let server = HttpServer::new(move || {
+        let index_page = maintenance_page.clone();
+        let fallback_page = maintenance_page.clone();
         App::new()
             // Logging for Actix with more details
             .wrap(TracingLogger::default())
-            // Add error handlers for 404 Not Found errors to redirect to root
-            .wrap(ErrorHandlers::new().handler(StatusCode::NOT_FOUND, redirect_to_root))
             // Store the app state
             .app_data(app_state.clone())
+            .app_data(auth::BehindBuiltinProxy(behind_proxy))
+            .route("/login", web::post().to(auth::login))
+            .route("/logout", web::post().to(auth::logout))
+            .route("/session", web::get().to(auth::session))
             // Serve the download endpoint and static files
-            .route("/download", web::post().to(handlers::download_and_extract))
-            .route("/upload", web::post().to(handlers::upload_and_extract))
-            .route("/events", web::get().to(events::sse_events))
-            .route("/dev-info", web::get().to(handlers::info))
-            .service(Files::new("/", &static_files_dir).index_file("index.html"))
+            .service(
+                web::resource("/download")
+                    .wrap(from_fn(auth::require_auth))
+                    .route(web::post().to(handlers::download_and_extract)),
+            )
+            .service(
+                web::resource("/upload")
+                    .wrap(from_fn(auth::require_auth))
+                    .route(web::post().to(handlers::upload_and_extract)),
+            )
+            .service(
+                web::resource("/api/v1/installs")
+                    .wrap(from_fn(auth::require_auth))
+                    .route(web::post().to(handlers::create_install))
+                    .route(web::get().to(handlers::install_jobs)),
+            )
+            .service(
+                web::resource("/api/v1/installs/{id}")
+                    .wrap(from_fn(auth::require_auth))
+                    .route(web::get().to(handlers::install_job)),
+            )
+            .service(
+                web::resource("/events")
+                    .wrap(from_fn(auth::require_auth))
+                    .route(web::get().to(events::sse_events)),
+            )
+            .service(
+                web::resource("/logs")
+                    .wrap(from_fn(auth::require_auth))
+                    .route(web::get().to(logs::sse_logs)),
+            )
+            .service(
+                web::resource("/dev-info")
+                    .wrap(from_fn(auth::require_auth))
+                    .route(web::get().to(status::status)),
+            )
+            .service(
+                web::resource("/install-status")
+                    .wrap(from_fn(auth::require_auth))
+                    .route(web::get().to(handlers::install_status)),
+            )
+            .route("/healthz", web::get().to(readiness::healthz))
+            .route("/readyz", web::get().to(readiness::readyz))
+            .route("/progress", web::get().to(events::sse_public_progress))
+            // Once Foundry is installed, the installer makes way for the maintenance page
+            .route(
+                "/",
+                web::get()
+                    .guard(guard::fn_guard(|ctx| {
+                        ctx.app_data::<web::Data<AppState>>()
+                            .is_some_and(|state| !state.installer_active())
+                    }))
+                    .to(move || handlers::maintenance_page(index_page.clone())),
+            )
+            .service(
+                Files::new("/", &static_files_dir)
+                    .index_file("index.html")
+                    .default_handler(web::to(move |data: web::Data<AppState>| {
+                        handlers::fallback_page(fallback_page.clone(), data)
+                    })),
+            )
     })
-    .bind((server_host, server_port))?
-    .run();
+    .bind((host.to_string(), port))
+    .map_err(|e| ports::bind_error(port, e))?;
 
-    let server_handle = server.handle();
-
-    // Spawn a task to wait for the shutdown signal
-    tokio::spawn(async move {
-        // If we receive the shutdown signal, stop the server gracefully
-        if rx.await.is_ok() {
-            info!("Received shutdown signal, stopping Actix server");
-            server_handle.stop(true).await;
-            info!("Actix server stopped, transitioning to process management mode");
-        }
-    });
-
-    // Setup signal handlers for SIGTERM and SIGINT
-    setup_signal_handlers();
+    let bound_port = server
+        .addrs()
+        .first()
+        .map(|addr| addr.port())
+        .unwrap_or(port);
+    info!("Server is running on {}:{}", host, bound_port);
+    debug!("Debug logging is enabled");
 
-    Ok(tokio::spawn(server))
+    let server = server.run();
+    let server_handle = server.handle();
+    Ok((server_handle, bound_port, tokio::spawn(server)))
 }
 
-// Function to redirect 404 responses to the root path
-fn redirect_to_root<B>(res: ServiceResponse<B>) -> Result<ErrorHandlerResponse<B>> {
-    let response = HttpResponse::Found()
-        .insert_header((header::LOCATION, "/"))
-        .finish()
-        .map_into_right_body();
+/// Serves the management API on its own port for the life of the container,
+/// whether the installer or Foundry holds the public port
+pub fn start_management_server(
+    config: &AppConfig,
+    app_state: web::Data<AppState>,
+    tls: Option<Arc<ServerConfig>>,
+) -> std::io::Result<()> {
+    info!(
+        "🛠️ Management API is running on {}://{}:{}",
+        if tls.is_some() { "https" } else { "http" },
+        config.server_host,
+        config.management_port
+    );
 
-    Ok(ErrorHandlerResponse::Response(res.into_response(response)))
+    let server = HttpServer::new(move || {
+        App::new()
+            .wrap(TracingLogger::default())
+            .app_data(app_state.clone())
+            .service(
+                web::scope("/api")
+                    .wrap(from_fn(auth::require_auth))
+                    .route("/status", web::get().to(handlers::info))
+                    .route("/v1/status", web::get().to(status::status))
+                    .route("/v1/installs", web::post().to(handlers::create_install))
+                    .route("/v1/installs", web::get().to(handlers::install_jobs))
+                    .route("/v1/installs/{id}", web::get().to(handlers::install_job))
+                    .route("/restart", web::post().to(handlers::restart))
+                    .route("/logs", web::get().to(handlers::recent_logs))
+                    .route("/logs/stream", web::get().to(logs::sse_logs))
+                    .route("/events", web::get().to(events::sse_events))
+                    .route("/upgrade", web::post().to(handlers::upgrade))
+                    .route("/backup", web::post().to(handlers::backup))
+                    .route("/schedule", web::get().to(handlers::schedule_info)),
+            )
+            .service(
+                web::resource("/metrics")
+                    .wrap(from_fn(auth::require_auth))
+                    .route(web::get().to(metrics::metrics)),
+            )
+            .route("/healthz", web::get().to(readiness::healthz))
+            .route("/readyz", web::get().to(readiness::readyz))
+    })
+    .workers(2);
+    let address = (config.server_host.clone(), config.management_port);
+    let server = match tls {
+        Some(tls) => server.bind_rustls_0_23(address, (*tls).clone()),
+        None => server.bind(address),
+    }
+    .map_err(|e| ports::bind_error(config.management_port, e))?
+    .run();
+
+    tokio::spawn(server);
+    Ok(())
 }
 
-fn setup_signal_handlers() {
-    #[cfg(unix)]
-    {
-        use tokio::signal::unix::{SignalKind, signal};
-        use tracing::info;
+/// Serves the "scheduled off" page on Foundry's port while the play window is closed.
+/// The returned handle stops the server before Foundry takes the port back.
+pub fn start_offline_server(
+    config: &AppConfig,
+    app_state: web::Data<AppState>,
+) -> std::io::Result<ServerHandle> {
+    let offline_page = format!("{}/offline.html", config.static_files_dir);
+    // Behind the built-in proxy Foundry's port is internal, and the proxy forwards to it
+    let host = if config.builtin_proxy {
+        "127.0.0.1".to_string()
+    } else {
+        config.server_host.clone()
+    };
+    info!(
+        "Serving scheduled-off page on {}:{}",
+        host, config.foundry_port
+    );
 
-        // Handle SIGTERM
-        tokio::spawn(async move {
-            let mut sigterm = signal(SignalKind::terminate()).unwrap();
-            sigterm.recv().await;
-            info!("Received SIGTERM, initiating shutdown");
-            std::process::exit(0);
-        });
+    let server = HttpServer::new(move || {
+        let offline_page = offline_page.clone();
+        App::new()
+            .wrap(TracingLogger::default())
+            .app_data(app_state.clone())
+            .route("/api/schedule", web::get().to(handlers::schedule_info))
+            .route("/healthz", web::get().to(readiness::healthz))
+            .route("/readyz", web::get().to(readiness::readyz))
+            .default_service(web::to(move |data: web::Data<AppState>| {
+                handlers::offline_page(offline_page.clone(), data)
+            }))
+    })
+    .workers(1)
+    .bind((host, config.foundry_port))
+    .map_err(|e| ports::bind_error(config.foundry_port, e))?
+    .run();
 
-        // Handle SIGINT
-        tokio::spawn(async move {
-            let mut sigint = signal(SignalKind::interrupt()).unwrap();
-            sigint.recv().await;
-            info!("Received SIGINT, initiating shutdown");
-            std::process::exit(0);
-        });
-    }
+    let handle = server.handle();
+    tokio::spawn(server);
+    Ok(handle)
 }
@@ -0,0 +1,309 @@
+use actix_web::{HttpResponse, Responder, web};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+use crate::config::AppConfig;
+use crate::jobs::InstallJob;
+use crate::launch::ExitReport;
+use crate::options::OptionsPrecedence;
+use crate::provision::SECRET_VARS;
+use crate::readiness::{ReadinessSnapshot, ReadinessState};
+use crate::server::AppState;
+use crate::tls::TlsSource;
+use crate::utils::paths;
+
+const REDACTED: &str = "[redacted]";
+
+/// Fragments of flag names whose values are never shown
+const SECRET_FLAG_HINTS: [&str; 4] = ["key", "password", "secret", "token"];
+
+/// What the watcher is doing with the public port
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Waiting for a Foundry archive, or installing one
+    Installer,
+    /// Foundry accepts connections
+    Running,
+    /// A new Foundry release is being downloaded or installed
+    Upgrading,
+    /// Foundry is installed but starting, restarting or kept off by a schedule
+    Maintenance,
+}
+
+impl Mode {
+    fn current(app_state: &AppState, readiness: ReadinessState, running: bool) -> Self {
+        if app_state.upgrading.load(Ordering::SeqCst) {
+            Mode::Upgrading
+        } else if app_state.installer_active() {
+            Mode::Installer
+        } else if running && matches!(readiness, ReadinessState::Listening | ReadinessState::Ready)
+        {
+            Mode::Running
+        } else {
+            Mode::Maintenance
+        }
+    }
+}
+
+/// Document served by `/api/v1/status`
+#[derive(Debug, Serialize)]
+pub struct StatusDocument {
+    pub watcher: WatcherInfo,
+    pub mode: Mode,
+    pub install_job: Option<InstallJob>,
+    /// Why the installed Foundry is considered broken
+    pub install_failure: Option<String>,
+    pub foundry: FoundryInstallation,
+    pub supervisor: SupervisorInfo,
+    pub disk: Vec<DiskSpace>,
+    pub config: ConfigSummary,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WatcherInfo {
+    pub version: &'static str,
+    pub started_at: DateTime<Utc>,
+    pub uptime_secs: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FoundryInstallation {
+    pub installed: bool,
+    pub version: Option<String>,
+    pub script_path: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SupervisorInfo {
+    pub pid: Option<u32>,
+    pub started_at: Option<DateTime<Utc>>,
+    /// Seconds since the current process was spawned
+    pub uptime_secs: Option<i64>,
+    pub restart_count: u32,
+    pub last_exit: Option<ExitReport>,
+    pub command_line: Vec<String>,
+    pub scheduled_off_until: Option<DateTime<Utc>>,
+    pub next_restart: Option<DateTime<Utc>>,
+    pub readiness: ReadinessSnapshot,
+}
+
+/// Space left on the filesystem holding one of the watcher's directories
+#[derive(Debug, Serialize)]
+pub struct DiskSpace {
+    /// `application` or `data`
+    pub name: &'static str,
+    pub path: String,
+    pub free_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+}
+
+/// The settings the watcher runs with, minus anything secret
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigSummary {
+    pub application_port: u16,
+    pub foundry_port: u16,
+    pub management_port: u16,
+    pub builtin_proxy: bool,
+    pub hostname: Option<String>,
+    pub route_prefix: Option<String>,
+    pub proxy_ssl: bool,
+    /// `files`, `self-signed` or none
+    pub tls: Option<&'static str>,
+    pub application_dir: String,
+    pub data_dir: String,
+    pub backup_dir: PathBuf,
+    pub backup_keep: usize,
+    pub options_precedence: OptionsPrecedence,
+    pub foundry_args: Vec<String>,
+    pub node_binary: String,
+    pub node_flags: Vec<String>,
+    pub run_as: Option<String>,
+    pub watchdog: bool,
+    pub startup_failure_limit: u32,
+    /// Every secret the watcher reads, `[redacted]` when it is set
+    pub secrets: BTreeMap<&'static str, Option<&'static str>>,
+}
+
+impl ConfigSummary {
+    pub fn new(config: &AppConfig) -> Self {
+        let options = &config.foundry_options;
+        let secrets = SECRET_VARS
+            .into_iter()
+            .map(|name| {
+                let set =
+                    env::var_os(name).is_some() || env::var_os(format!("{}_FILE", name)).is_some();
+                (name, set.then_some(REDACTED))
+            })
+            .collect();
+
+        Self {
+            application_port: config.server_port,
+            foundry_port: config.foundry_port,
+            management_port: config.management_port,
+            builtin_proxy: config.builtin_proxy,
+            hostname: options.hostname.clone(),
+            route_prefix: options.route_prefix.clone(),
+            proxy_ssl: options.proxy_ssl,
+            tls: config.tls.as_ref().map(|tls| match tls {
+                TlsSource::Files { .. } => "files",
+                TlsSource::SelfSigned { .. } => "self-signed",
+            }),
+            application_dir: config.target_dir.clone(),
+            data_dir: options.data_path.clone(),
+            backup_dir: config.backup_dir.clone(),
+            backup_keep: config.backup_keep,
+            options_precedence: config.options_precedence,
+            foundry_args: redact_args(&options.args()),
+            node_binary: config.node_binary.clone(),
+            node_flags: config.node_flags.clone(),
+            run_as: config
+                .run_as
+                .map(|run_as| format!("{}:{}", run_as.uid, run_as.gid)),
+            watchdog: config.watchdog.enabled,
+            startup_failure_limit: config.startup_failure_limit,
+            secrets,
+        }
+    }
+}
+
+/// Hides the values of flags that look like credentials, e.g. `--adminKey=...`.
+/// Both `--flag=value` and `--flag value` are handled.
+pub fn redact_args(args: &[String]) -> Vec<String> {
+    let is_secret = |flag: &str| {
+        let flag = flag.to_lowercase();
+        flag.starts_with('-') && SECRET_FLAG_HINTS.iter().any(|hint| flag.contains(hint))
+    };
+
+    let mut redacted = Vec::with_capacity(args.len());
+    let mut hide_next = false;
+    for arg in args {
+        if hide_next && !arg.starts_with('-') {
+            redacted.push(REDACTED.to_string());
+            hide_next = false;
+            continue;
+        }
+        hide_next = false;
+        match arg.split_once('=') {
+            Some((flag, _)) if is_secret(flag) => redacted.push(format!("{}={}", flag, REDACTED)),
+            Some(_) => redacted.push(arg.clone()),
+            None => {
+                hide_next = is_secret(arg);
+                redacted.push(arg.clone());
+            }
+        }
+    }
+    redacted
+}
+
+/// Free and total bytes of the filesystem `path` is on
+#[allow(clippy::unnecessary_cast)] // the statvfs field types differ between platforms
+fn disk_space(path: &Path) -> Option<(u64, u64)> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let block = stat.f_frsize as u64;
+    Some((stat.f_bavail as u64 * block, stat.f_blocks as u64 * block))
+}
+
+fn disk(name: &'static str, path: &str) -> DiskSpace {
+    let space = disk_space(Path::new(path));
+    DiskSpace {
+        name,
+        path: path.to_string(),
+        free_bytes: space.map(|(free, _)| free),
+        total_bytes: space.map(|(_, total)| total),
+    }
+}
+
+/// Assembles the status document from the shared state
+pub fn document(app_state: &AppState) -> StatusDocument {
+    let now = Utc::now();
+    let supervisor = app_state.supervisor.lock().unwrap().clone();
+    let readiness = app_state.readiness.snapshot();
+    let config = &app_state.config_summary;
+
+    StatusDocument {
+        watcher: WatcherInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            started_at: app_state.started_at,
+            uptime_secs: (now - app_state.started_at).num_seconds(),
+        },
+        mode: Mode::current(app_state, readiness.state, supervisor.pid.is_some()),
+        install_job: app_state.installs.current(),
+        install_failure: app_state.install_failure.lock().unwrap().clone(),
+        foundry: FoundryInstallation {
+            installed: paths::FOUNDRY_SCRIPT_PATH.exists(),
+            version: paths::foundry_version(&config.application_dir),
+            script_path: paths::FOUNDRY_SCRIPT_PATH.clone(),
+        },
+        supervisor: SupervisorInfo {
+            pid: supervisor.pid,
+            started_at: supervisor.started_at,
+            uptime_secs: supervisor
+                .pid
+                .and(supervisor.started_at)
+                .map(|started_at| (now - started_at).num_seconds()),
+            restart_count: supervisor.restart_count,
+            last_exit: supervisor.last_exit,
+            command_line: redact_args(&supervisor.command_line),
+            scheduled_off_until: supervisor.scheduled_off_until,
+            next_restart: supervisor.next_restart,
+            readiness,
+        },
+        disk: vec![
+            disk("application", &config.application_dir),
+            disk("data", &config.data_dir),
+        ],
+        config: config.clone(),
+    }
+}
+
+/// Serves the status document
+pub async fn status(app_state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(document(&app_state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_args() {
+        let args: Vec<String> = [
+            "--port=30000",
+            "--adminKey=hunter2",
+            "--license-key",
+            "ABCD-1234",
+            "--world",
+            "sandbox",
+            "--secretive",
+            "--noupnp",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+
+        assert_eq!(
+            redact_args(&args),
+            [
+                "--port=30000",
+                "--adminKey=[redacted]",
+                "--license-key",
+                "[redacted]",
+                "--world",
+                "sandbox",
+                "--secretive",
+                "--noupnp",
+            ]
+        );
+    }
+}
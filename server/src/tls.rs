@@ -0,0 +1,190 @@
+use anyhow::{Context, Result, anyhow};
+use rustls::ServerConfig;
+use rustls::crypto::{CryptoProvider, aws_lc_rs};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use tokio::time::{Duration, sleep};
+use tracing::{info, warn};
+
+/// How often the certificate files are checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Where the certificate for the management API and the proxy comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum TlsSource {
+    /// PEM files provided by the user, reloaded whenever they change
+    Files { cert: PathBuf, key: PathBuf },
+    /// A certificate generated for `hostname` and kept in `dir` across restarts
+    SelfSigned { hostname: String, dir: PathBuf },
+}
+
+/// Hands out whichever certificate was loaded last, so renewed files take effect
+/// without restarting the servers
+#[derive(Debug)]
+struct CertResolver {
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+/// Loads the certificate, generating it first in self-signed mode, and keeps
+/// watching the files for changes. The returned config is shared by every listener.
+pub fn server_config(source: &TlsSource) -> Result<Arc<ServerConfig>> {
+    let (cert, key) = match source {
+        TlsSource::Files { cert, key } => (cert.clone(), key.clone()),
+        TlsSource::SelfSigned { hostname, dir } => ensure_self_signed(hostname, dir)?,
+    };
+
+    let resolver = Arc::new(CertResolver {
+        current: RwLock::new(Arc::new(load_certified_key(&cert, &key)?)),
+    });
+    info!(
+        "🔐 Serving HTTPS with the certificate from {}",
+        cert.display()
+    );
+
+    let config = ServerConfig::builder_with_provider(Arc::new(provider()))
+        .with_safe_default_protocol_versions()
+        .context("Failed to set up TLS")?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver.clone());
+
+    tokio::spawn(watch(cert, key, resolver));
+    Ok(Arc::new(config))
+}
+
+fn provider() -> CryptoProvider {
+    aws_lc_rs::default_provider()
+}
+
+/// Reads a PEM certificate chain and the matching private key
+pub fn load_certified_key(cert: &Path, key: &Path) -> Result<CertifiedKey> {
+    let chain = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("Failed to read certificates from {}", cert.display()))?;
+    if chain.is_empty() {
+        return Err(anyhow!("{} contains no certificates", cert.display()));
+    }
+    let private_key = PrivateKeyDer::from_pem_file(key)
+        .with_context(|| format!("Failed to read a private key from {}", key.display()))?;
+
+    CertifiedKey::from_der(chain, private_key, &provider()).with_context(|| {
+        format!(
+            "The key in {} does not fit the certificate in {}",
+            key.display(),
+            cert.display()
+        )
+    })
+}
+
+/// Returns the certificate and key for `hostname` in `dir`, generating them on
+/// first use. Keeping them means browsers only have to accept the certificate once.
+fn ensure_self_signed(hostname: &str, dir: &Path) -> Result<(PathBuf, PathBuf)> {
+    let name: String = hostname
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let cert = dir.join(format!("{}.crt", name));
+    let key = dir.join(format!("{}.key", name));
+    if cert.is_file() && key.is_file() {
+        return Ok((cert, key));
+    }
+
+    let mut names = vec![hostname.to_string()];
+    for extra in ["localhost", "127.0.0.1"] {
+        if hostname != extra {
+            names.push(extra.to_string());
+        }
+    }
+    let generated = rcgen::generate_simple_self_signed(names)
+        .context("Failed to generate a self-signed certificate")?;
+
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    fs::write(&cert, generated.cert.pem())
+        .with_context(|| format!("Failed to write {}", cert.display()))?;
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&key)
+        .and_then(|mut file| file.write_all(generated.signing_key.serialize_pem().as_bytes()))
+        .with_context(|| format!("Failed to write {}", key.display()))?;
+
+    info!(
+        "🔐 Generated a self-signed certificate for {} in {}",
+        hostname,
+        cert.display()
+    );
+    Ok((cert, key))
+}
+
+/// Swaps in the certificate whenever either file changes. A pair that fails to
+/// load leaves the current certificate in place until the files change again.
+async fn watch(cert: PathBuf, key: PathBuf, resolver: Arc<CertResolver>) {
+    let mut last = modified(&cert, &key);
+    loop {
+        sleep(RELOAD_INTERVAL).await;
+        let current = modified(&cert, &key);
+        if current == last {
+            continue;
+        }
+        last = current;
+
+        match load_certified_key(&cert, &key) {
+            Ok(loaded) => {
+                *resolver.current.write().unwrap() = Arc::new(loaded);
+                info!("🔐 Reloaded the TLS certificate from {}", cert.display());
+            }
+            Err(e) => warn!("⚠️ Keeping the current TLS certificate: {:#}", e),
+        }
+    }
+}
+
+fn modified(cert: &Path, key: &Path) -> Option<(SystemTime, SystemTime)> {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    Some((modified(cert).ok()?, modified(key).ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_signed_is_generated_once() {
+        let dir = std::env::temp_dir().join(format!("watcher-tls-{}", std::process::id()));
+        let (cert, key) = ensure_self_signed("vtt.example", &dir).unwrap();
+        assert!(load_certified_key(&cert, &key).is_ok());
+
+        let pem = fs::read_to_string(&cert).unwrap();
+        assert_eq!(
+            ensure_self_signed("vtt.example", &dir).unwrap(),
+            (cert.clone(), key.clone())
+        );
+        assert_eq!(fs::read_to_string(&cert).unwrap(), pem);
+
+        // A key from another certificate is rejected
+        let (_, other_key) = ensure_self_signed("other.example", &dir).unwrap();
+        assert!(load_certified_key(&cert, &other_key).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
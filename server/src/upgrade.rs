@@ -0,0 +1,85 @@
+use anyhow::{Result, anyhow};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::sync::broadcast;
+use tracing::{debug, info};
+
+use crate::events::ProgressEvent;
+use crate::extractor::ExtractorService;
+use crate::metrics::Metrics;
+use crate::utils::paths;
+
+/// Directory inside the application directory the update is extracted to first
+const STAGING_DIR: &str = ".upgrade";
+
+/// Where a downloaded update waits until Foundry is stopped
+pub fn archive_path(target_dir: &str) -> PathBuf {
+    Path::new(target_dir).join(".upgrade.zip")
+}
+
+/// Replaces the Foundry installation in `target_dir` with the contents of `archive`.
+///
+/// The archive is extracted next to the current installation and only swapped in
+/// once it is known to contain Foundry, so a bad download leaves the old version
+/// in place. Foundry must not be running.
+pub async fn install_update(
+    target_dir: &str,
+    archive: &Path,
+    event_tx: broadcast::Sender<ProgressEvent>,
+    metrics: &Metrics,
+) -> Result<()> {
+    let target = Path::new(target_dir);
+    let staging = target.join(STAGING_DIR);
+    if fs::metadata(&staging).await.is_ok() {
+        fs::remove_dir_all(&staging).await?;
+    }
+
+    let extracted = ExtractorService::extract_zip(
+        archive.to_string_lossy().to_string(),
+        staging.to_string_lossy().to_string(),
+        event_tx,
+        metrics,
+    )
+    .await;
+    let _ = fs::remove_file(archive).await;
+    if let Err(e) = extracted {
+        let _ = fs::remove_dir_all(&staging).await;
+        return Err(e.into());
+    }
+
+    if !paths::resolve_foundry_script_path(&staging.to_string_lossy()).exists() {
+        let _ = fs::remove_dir_all(&staging).await;
+        return Err(anyhow!("The archive does not contain FoundryVTT's main.js"));
+    }
+
+    info!(
+        "Replacing the FoundryVTT installation in {}",
+        target.display()
+    );
+    clear_installation(target, Some(&staging)).await?;
+
+    let mut entries = fs::read_dir(&staging).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        fs::rename(entry.path(), target.join(entry.file_name())).await?;
+    }
+    fs::remove_dir(&staging).await?;
+    Ok(())
+}
+
+/// Removes everything in the application directory except `keep`
+pub async fn clear_installation(target: &Path, keep: Option<&Path>) -> Result<()> {
+    let mut entries = fs::read_dir(target).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if keep == Some(path.as_path()) {
+            continue;
+        }
+        debug!("Removing {}", path.display());
+        if entry.file_type().await?.is_dir() {
+            fs::remove_dir_all(&path).await?;
+        } else {
+            fs::remove_file(&path).await?;
+        }
+    }
+    Ok(())
+}
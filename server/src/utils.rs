@@ -41,6 +41,195 @@ pub mod paths {
         debug!("Using new Foundry VTT path: {:?}", new_path);
         new_path
     }
+
+    /// Reads the installed Foundry VTT version from its package.json,
+    /// next to whichever main.js layout is in use
+    pub fn foundry_version(app_dir: &str) -> Option<String> {
+        let script = resolve_foundry_script_path(app_dir);
+        let package = std::fs::read_to_string(script.with_file_name("package.json")).ok()?;
+        let package: serde_json::Value = serde_json::from_str(&package).ok()?;
+        package.get("version")?.as_str().map(str::to_string)
+    }
+}
+
+/// Helpers for reading process information from /proc
+pub mod procfs {
+    use std::fs;
+
+    /// Resident set size of `pid` in kilobytes
+    pub fn rss_kb(pid: u32) -> Option<u64> {
+        let status = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+        parse_status_kb(&status, "VmRSS")
+    }
+
+    /// Reads a `Key:   1234 kB` entry from the contents of `/proc/<pid>/status`
+    pub fn parse_status_kb(status: &str, key: &str) -> Option<u64> {
+        status.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            if name != key {
+                return None;
+            }
+            value.split_whitespace().next()?.parse().ok()
+        })
+    }
+
+    /// CPU time `pid` has spent in user and kernel mode, in seconds
+    pub fn cpu_seconds(pid: u32) -> Option<f64> {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        let ticks = parse_stat_cpu_ticks(&stat)?;
+        let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+        (ticks_per_second > 0).then(|| ticks as f64 / ticks_per_second as f64)
+    }
+
+    /// Adds up utime and stime from the contents of `/proc/<pid>/stat`. The process
+    /// name may contain spaces and parentheses, so fields are counted after its end.
+    pub fn parse_stat_cpu_ticks(stat: &str) -> Option<u64> {
+        let (_, fields) = stat.rsplit_once(')')?;
+        let fields: Vec<&str> = fields.split_whitespace().collect();
+        let utime: u64 = fields.get(11)?.parse().ok()?;
+        let stime: u64 = fields.get(12)?.parse().ok()?;
+        Some(utime + stime)
+    }
+
+    /// Inodes of the sockets listening on `port`, over IPv4 and IPv6
+    pub fn listening_socket_inodes(port: u16) -> Vec<u64> {
+        ["/proc/net/tcp", "/proc/net/tcp6"]
+            .iter()
+            .filter_map(|table| fs::read_to_string(table).ok())
+            .flat_map(|table| parse_listening_inodes(&table, port))
+            .collect()
+    }
+
+    /// Reads the inodes of sockets in the LISTEN state on `port` from the
+    /// contents of `/proc/net/tcp` or `/proc/net/tcp6`
+    pub fn parse_listening_inodes(table: &str, port: u16) -> Vec<u64> {
+        table
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let (_, local_port) = fields.get(1)?.rsplit_once(':')?;
+                let listening = *fields.get(3)? == "0A";
+                if !listening || u16::from_str_radix(local_port, 16).ok()? != port {
+                    return None;
+                }
+                fields.get(9)?.parse().ok()
+            })
+            .collect()
+    }
+
+    /// Finds the process holding the socket with `inode` open, returning its pid and name.
+    /// Processes of other users are only visible when running as root.
+    pub fn socket_owner(inode: u64) -> Option<(u32, String)> {
+        let target = format!("socket:[{}]", inode);
+        fs::read_dir("/proc")
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .find_map(|entry| {
+                let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+                let owns_socket = fs::read_dir(entry.path().join("fd"))
+                    .ok()?
+                    .filter_map(|fd| fd.ok())
+                    .any(|fd| {
+                        fs::read_link(fd.path())
+                            .is_ok_and(|link| link.as_os_str() == target.as_str())
+                    });
+                if !owns_socket {
+                    return None;
+                }
+                let name = fs::read_to_string(entry.path().join("comm")).unwrap_or_default();
+                Some((pid, name.trim().to_string()))
+            })
+    }
+}
+
+/// Helpers for replacing files that Foundry or its users care about
+pub mod files {
+    use anyhow::{Context, Result};
+    use chrono::Local;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use tracing::{debug, warn};
+
+    /// Atomically replaces `path` with `content`, first copying the previous file to
+    /// `<name>.<timestamp>-<n>.bak` and keeping only the newest `keep` backups
+    pub fn write_with_backup(path: &Path, content: &str, keep: usize) -> Result<()> {
+        let dir = path
+            .parent()
+            .with_context(|| format!("{} has no parent directory", path.display()))?;
+        let name = path
+            .file_name()
+            .with_context(|| format!("{} has no file name", path.display()))?
+            .to_string_lossy()
+            .to_string();
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+        if path.exists() {
+            let prefix = format!("{}.", name);
+            let backup = backup_path(dir, &prefix, ".bak");
+            fs::copy(path, &backup)
+                .with_context(|| format!("Failed to back up {}", path.display()))?;
+            prune_backups(dir, &prefix, ".bak", keep);
+        }
+
+        write_atomic(path, content)
+    }
+
+    /// Replaces `path` with `content` through a temporary file, so readers never see half a file
+    pub fn write_atomic(path: &Path, content: &str) -> Result<()> {
+        let name = path
+            .file_name()
+            .with_context(|| format!("{} has no file name", path.display()))?
+            .to_string_lossy();
+        let temp = path.with_file_name(format!(".{}.tmp", name));
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(&temp, content).with_context(|| format!("Failed to write {}", temp.display()))?;
+        fs::rename(&temp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Path for a new backup in `dir` named `<prefix><timestamp>-<n><suffix>`. The
+    /// timestamp has milliseconds and `n` counts up until the name is free, so backups
+    /// taken in quick succession never replace each other.
+    pub fn backup_path(dir: &Path, prefix: &str, suffix: &str) -> PathBuf {
+        let timestamp = Local::now().format("%Y%m%d%H%M%S%3f").to_string();
+        let mut counter = 0;
+        loop {
+            let path = dir.join(format!("{}{}-{:03}{}", prefix, timestamp, counter, suffix));
+            if !path.exists() {
+                return path;
+            }
+            counter += 1;
+        }
+    }
+
+    /// Removes all but the newest `keep` files in `dir` named `<prefix>...<suffix>`
+    pub fn prune_backups(dir: &Path, prefix: &str, suffix: &str, keep: usize) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        let mut backups: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name().is_some_and(|file_name| {
+                    let file_name = file_name.to_string_lossy();
+                    file_name.starts_with(prefix) && file_name.ends_with(suffix)
+                })
+            })
+            .collect();
+        // Timestamps sort chronologically
+        backups.sort();
+        let excess = backups.len().saturating_sub(keep);
+        for old in &backups[..excess] {
+            match fs::remove_file(old) {
+                Ok(()) => debug!("Removed old backup {}", old.display()),
+                Err(e) => warn!("⚠️ Failed to remove old backup {}: {}", old.display(), e),
+            }
+        }
+    }
 }
 
 /// Run a system command and return its output
@@ -116,6 +305,59 @@ mod tests {
         fs::remove_dir_all(&temp_dir).unwrap();
     }
 
+    #[test]
+    fn test_write_with_backup_keeps_every_version() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("backup_files_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&temp_dir);
+        let path = temp_dir.join("options.json");
+
+        // Written back to back, as on startup, within the same millisecond at times
+        for content in ["one", "two", "three"] {
+            files::write_with_backup(&path, content, 5).unwrap();
+        }
+
+        let mut backups: Vec<_> = fs::read_dir(&temp_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "bak"))
+            .collect();
+        backups.sort();
+        let contents: Vec<_> = backups
+            .iter()
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect();
+        assert_eq!(contents, ["one", "two"]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "three");
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_status_kb() {
+        let status = "Name:\tnode\nVmPeak:\t 2048000 kB\nVmRSS:\t  512344 kB\nThreads:\t11\n";
+        assert_eq!(procfs::parse_status_kb(status, "VmRSS"), Some(512344));
+        assert_eq!(procfs::parse_status_kb(status, "VmSwap"), None);
+    }
+
+    #[test]
+    fn test_parse_listening_inodes() {
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n\
+   0: 00000000:7530 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 48211 1 0000000000000000 100 0 0 10 0\n\
+   1: 0100007F:7530 0100007F:D2F4 01 00000000:00000000 00:00000000 00000000  1000        0 48290 1 0000000000000000 20 4 30 10 -1\n\
+   2: 00000000:115C 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 31007 1 0000000000000000 100 0 0 10 0\n";
+        assert_eq!(procfs::parse_listening_inodes(table, 30000), vec![48211]);
+        assert_eq!(procfs::parse_listening_inodes(table, 4444), vec![31007]);
+        assert!(procfs::parse_listening_inodes(table, 4445).is_empty());
+    }
+
+    #[test]
+    fn test_parse_stat_cpu_ticks() {
+        let stat = "4242 (node (main)) S 1 4242 4242 0 -1 4194560 91234 0 12 0 1500 250 0 0 20 0 11 0 3400 1203941376 128086 18446744073709551615\n";
+        assert_eq!(procfs::parse_stat_cpu_ticks(stat), Some(1750));
+        assert_eq!(procfs::parse_stat_cpu_ticks("4242 (node) S 1"), None);
+    }
+
     #[test]
     fn test_resolve_foundry_script_path_neither_exists() {
         // Test with a directory where neither file exists
@@ -0,0 +1,305 @@
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::str::FromStr;
+use tokio::time::{Duration, Instant, interval};
+use tracing::{debug, error, info, warn};
+
+use crate::config::{env_flag, env_parse};
+use crate::readiness;
+use crate::server::AppState;
+use crate::utils::procfs;
+
+/// Number of log lines captured in a watchdog report
+const REPORT_LOG_LINES: usize = 50;
+
+/// How the watchdog decides whether Foundry is still alive
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LivenessProbe {
+    /// Foundry accepts TCP connections on its port
+    Tcp,
+    /// Foundry answers an HTTP request with any status code
+    Http,
+}
+
+impl FromStr for LivenessProbe {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "tcp" => Ok(LivenessProbe::Tcp),
+            "http" => Ok(LivenessProbe::Http),
+            other => Err(format!("unknown liveness probe: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchdogConfig {
+    pub enabled: bool,
+    pub probe: LivenessProbe,
+    /// Consecutive failed probes that count as a hang
+    pub failures: usize,
+    /// The failures must all fall within this window
+    pub window: Duration,
+    pub interval: Duration,
+    /// Failures are ignored until Foundry answered once or this much time has passed
+    pub startup_grace: Duration,
+}
+
+impl WatchdogConfig {
+    /// Reads the watchdog settings. Unparsable values are rejected by
+    /// `initialization::validate_env`, combinations by `validate`.
+    pub fn from_env() -> Self {
+        Self {
+            enabled: env_flag("WATCHDOG_ENABLED", true),
+            probe: env_parse("WATCHDOG_PROBE", LivenessProbe::Http),
+            failures: env_parse("WATCHDOG_FAILURES", 5),
+            window: Duration::from_secs(env_parse("WATCHDOG_WINDOW_SECS", 120)),
+            interval: Duration::from_secs(env_parse("WATCHDOG_INTERVAL_SECS", 15)),
+            startup_grace: Duration::from_secs(env_parse("WATCHDOG_STARTUP_GRACE_SECS", 300)),
+        }
+    }
+
+    /// Checks that enough failed probes can fall within the window to ever trigger
+    /// a restart, since failures older than the window are forgotten
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.failures == 0 {
+            errors.push("WATCHDOG_FAILURES must be at least 1".to_string());
+        }
+        if self.interval.is_zero() {
+            errors.push("WATCHDOG_INTERVAL_SECS must be at least 1".to_string());
+        }
+        if self.window.is_zero() {
+            errors.push("WATCHDOG_WINDOW_SECS must be at least 1".to_string());
+        }
+        if errors.is_empty() {
+            let needed = self.interval * (self.failures as u32 - 1);
+            if self.window < needed {
+                errors.push(format!(
+                    "WATCHDOG_WINDOW_SECS={} cannot hold {} failed probes {}s apart, it needs at least {}",
+                    self.window.as_secs(),
+                    self.failures,
+                    self.interval.as_secs(),
+                    needed.as_secs()
+                ));
+            }
+        }
+        errors
+    }
+}
+
+/// Diagnostics captured right before the watchdog terminates Foundry
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchdogReport {
+    pub triggered_at: DateTime<Utc>,
+    pub failed_probes: usize,
+    pub pid: Option<u32>,
+    pub rss_kb: Option<u64>,
+    pub last_log_lines: Vec<String>,
+}
+
+/// Tracks consecutive probe failures inside the configured window
+struct FailureWindow {
+    failures: VecDeque<Instant>,
+    threshold: usize,
+    window: Duration,
+}
+
+impl FailureWindow {
+    fn new(threshold: usize, window: Duration) -> Self {
+        Self {
+            failures: VecDeque::with_capacity(threshold),
+            threshold,
+            window,
+        }
+    }
+
+    fn record_success(&mut self) {
+        self.failures.clear();
+    }
+
+    /// Records a failure at `now` and returns true once the threshold is reached
+    fn record_failure(&mut self, now: Instant) -> bool {
+        self.failures.push_back(now);
+        while let Some(first) = self.failures.front() {
+            if now.duration_since(*first) > self.window {
+                self.failures.pop_front();
+            } else {
+                break;
+            }
+        }
+        self.failures.len() >= self.threshold
+    }
+
+    fn len(&self) -> usize {
+        self.failures.len()
+    }
+}
+
+async fn probe_alive(probe: LivenessProbe, client: &Client, port: u16, status_url: &str) -> bool {
+    match probe {
+        LivenessProbe::Tcp => readiness::port_accepts_connections(port).await,
+        LivenessProbe::Http => client
+            .get(status_url)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .is_ok(),
+    }
+}
+
+/// Resolves with a diagnostic report once Foundry has failed enough liveness probes.
+/// Never resolves when the watchdog is disabled.
+pub async fn watch(
+    config: &WatchdogConfig,
+    port: u16,
+    status_url: &str,
+    pid: Option<u32>,
+    app_state: web::Data<AppState>,
+) -> WatchdogReport {
+    if !config.enabled {
+        return std::future::pending().await;
+    }
+
+    let client = readiness::probe_client();
+    let started = Instant::now();
+    let mut armed = false;
+    let mut window = FailureWindow::new(config.failures, config.window);
+    let mut ticker = interval(config.interval);
+
+    loop {
+        ticker.tick().await;
+
+        if probe_alive(config.probe, &client, port, status_url).await {
+            if !armed {
+                debug!("Watchdog armed after first successful liveness probe");
+            }
+            armed = true;
+            window.record_success();
+            continue;
+        }
+
+        if !armed {
+            if started.elapsed() < config.startup_grace {
+                continue;
+            }
+            info!(
+                "FoundryVTT has not answered within the {}s startup grace period, arming watchdog",
+                config.startup_grace.as_secs()
+            );
+            armed = true;
+        }
+
+        let hung = window.record_failure(Instant::now());
+        warn!(
+            "🐕 Liveness probe failed ({}/{})",
+            window.len(),
+            config.failures
+        );
+        if hung {
+            let report = WatchdogReport {
+                triggered_at: Utc::now(),
+                failed_probes: window.len(),
+                pid,
+                rss_kb: pid.and_then(procfs::rss_kb),
+                last_log_lines: app_state
+                    .logs
+                    .tail(REPORT_LOG_LINES)
+                    .into_iter()
+                    .map(|line| line.line)
+                    .collect(),
+            };
+
+            error!(
+                "🐕 FoundryVTT is unresponsive after {} failed probes (PID: {:?}, RSS: {} kB)",
+                report.failed_probes,
+                report.pid,
+                report
+                    .rss_kb
+                    .map(|rss| rss.to_string())
+                    .unwrap_or_else(|| "unknown".to_string())
+            );
+            for line in &report.last_log_lines {
+                error!("  | {}", line);
+            }
+            return report;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_window_triggers_after_threshold() {
+        let mut window = FailureWindow::new(3, Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(!window.record_failure(start));
+        assert!(!window.record_failure(start + Duration::from_secs(10)));
+        assert!(window.record_failure(start + Duration::from_secs(20)));
+    }
+
+    #[test]
+    fn test_failure_window_resets_on_success() {
+        let mut window = FailureWindow::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(!window.record_failure(start));
+        window.record_success();
+        assert!(!window.record_failure(start + Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_failure_window_drops_old_failures() {
+        let mut window = FailureWindow::new(2, Duration::from_secs(30));
+        let start = Instant::now();
+        assert!(!window.record_failure(start));
+        assert!(!window.record_failure(start + Duration::from_secs(45)));
+        assert!(window.record_failure(start + Duration::from_secs(50)));
+    }
+
+    #[test]
+    fn test_failure_window_keeps_failures_at_window_edge() {
+        // Three probes 15 seconds apart span exactly the 30 second window
+        let mut window = FailureWindow::new(3, Duration::from_secs(30));
+        let start = Instant::now();
+        assert!(!window.record_failure(start));
+        assert!(!window.record_failure(start + Duration::from_secs(15)));
+        assert!(window.record_failure(start + Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_validate_watchdog_config() {
+        let config = |failures: usize, window: u64, interval: u64| WatchdogConfig {
+            enabled: true,
+            probe: LivenessProbe::Http,
+            failures,
+            window: Duration::from_secs(window),
+            interval: Duration::from_secs(interval),
+            startup_grace: Duration::from_secs(300),
+        };
+
+        assert!(config(3, 30, 15).validate().is_empty());
+        assert!(config(1, 1, 15).validate().is_empty());
+        let errors = config(3, 29, 15).validate();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("needs at least 30"));
+
+        let errors = config(0, 0, 0).validate();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors[0].contains("WATCHDOG_FAILURES"));
+        assert!(errors[1].contains("WATCHDOG_INTERVAL_SECS"));
+        assert!(errors[2].contains("WATCHDOG_WINDOW_SECS"));
+    }
+
+    #[test]
+    fn test_parse_liveness_probe() {
+        assert_eq!("TCP".parse::<LivenessProbe>(), Ok(LivenessProbe::Tcp));
+        assert_eq!("http".parse::<LivenessProbe>(), Ok(LivenessProbe::Http));
+        assert!("icmp".parse::<LivenessProbe>().is_err());
+    }
+}
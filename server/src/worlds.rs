@@ -0,0 +1,92 @@
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A world found in the data directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldInfo {
+    pub id: String,
+    pub title: Option<String>,
+}
+
+/// Directory Foundry keeps its worlds in
+pub fn worlds_dir(data_dir: &str) -> PathBuf {
+    Path::new(data_dir).join("Data").join("worlds")
+}
+
+/// Returns true when `Data/worlds/<id>/world.json` exists
+pub fn world_exists(data_dir: &str, id: &str) -> bool {
+    worlds_dir(data_dir).join(id).join("world.json").is_file()
+}
+
+/// Every world with a world.json, sorted by id
+pub fn list_worlds(data_dir: &str) -> Vec<WorldInfo> {
+    let mut worlds: Vec<WorldInfo> = fs::read_dir(worlds_dir(data_dir))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let manifest = fs::read_to_string(entry.path().join("world.json")).ok()?;
+            let title = serde_json::from_str::<Value>(&manifest)
+                .ok()
+                .and_then(|manifest| manifest.get("title")?.as_str().map(str::to_string));
+            Some(WorldInfo {
+                id: entry.file_name().to_string_lossy().to_string(),
+                title,
+            })
+        })
+        .collect();
+    worlds.sort_by(|a, b| a.id.cmp(&b.id));
+    worlds
+}
+
+/// Explains that `id` does not exist and lists the worlds that do
+pub fn missing_world_message(id: &str, worlds: &[WorldInfo]) -> String {
+    if worlds.is_empty() {
+        return format!(
+            "FOUNDRY_WORLD is {} but there are no worlds in the data directory yet",
+            id
+        );
+    }
+
+    let available: Vec<String> = worlds
+        .iter()
+        .map(|world| match &world.title {
+            Some(title) => format!("{} ({})", world.id, title),
+            None => world.id.clone(),
+        })
+        .collect();
+    format!(
+        "FOUNDRY_WORLD is {} but no such world exists. Available worlds: {}",
+        id,
+        available.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_world_message() {
+        let worlds = [
+            WorldInfo {
+                id: "curse-of-strahd".to_string(),
+                title: Some("Curse of Strahd".to_string()),
+            },
+            WorldInfo {
+                id: "sandbox".to_string(),
+                title: None,
+            },
+        ];
+        assert_eq!(
+            missing_world_message("strahd", &worlds),
+            "FOUNDRY_WORLD is strahd but no such world exists. \
+             Available worlds: curse-of-strahd (Curse of Strahd), sandbox"
+        );
+        assert_eq!(
+            missing_world_message("strahd", &[]),
+            "FOUNDRY_WORLD is strahd but there are no worlds in the data directory yet"
+        );
+    }
+}
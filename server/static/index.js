@@ -7,6 +7,8 @@
     eventSourceConnected: false,
     processingComplete: false,
     serverShuttingDown: false,
+    // Install job whose events the progress bar follows
+    jobId: null,
   };
 
   /**
@@ -75,9 +77,22 @@
             }),
       };
       const response = await fetch(endpoint, options);
+      if (response.status === 401) {
+        window.location.href = "/login.html";
+        return;
+      }
+      if (response.status === 403) {
+        showToast("Installing needs the operator role.", "red");
+        showProcessingUI(false);
+        return;
+      }
       if (!response.ok) {
         throw new Error(`Server responded with status ${response.status}`);
       }
+      if (response.status === 202) {
+        const job = await response.json();
+        state.jobId = job.id;
+      }
       showToast(
         "Process initiated. The server will exit once the operation is complete.",
         "green",
@@ -86,6 +101,65 @@
       console.error(`Error with ${endpoint}:`, error);
       showToast("Failed to process request. Please try again.", "red");
       showProcessingUI(false);
+      showFailureBanner();
+    }
+  };
+
+  /**
+   * Sends the browser to the login page unless it has a session.
+   * @returns {Promise<boolean>} Whether the browser is signed in.
+   */
+  const ensureSignedIn = async () => {
+    try {
+      const response = await fetch("/session", { cache: "no-store" });
+      if (response.status === 401) {
+        window.location.href = "/login.html";
+        return false;
+      }
+    } catch (error) {
+      console.warn("Failed to check the session:", error);
+    }
+    return true;
+  };
+
+  /**
+   * Shows why the installer is back when the installed Foundry VTT is broken.
+   */
+  const showFailureBanner = async () => {
+    const banner = document.getElementById("failure-banner");
+    if (!banner) return;
+    try {
+      const response = await fetch("/install-status", { cache: "no-store" });
+      const status = await response.json();
+      banner.textContent = status.failure || "";
+      banner.style.display = status.failure ? "block" : "none";
+    } catch (error) {
+      console.warn("Failed to check the installation status:", error);
+    }
+  };
+
+  /**
+   * Picks up an install that is still running, e.g. after the page was reloaded.
+   */
+  const resumeRunningJob = async () => {
+    try {
+      const response = await fetch("/api/v1/installs", { cache: "no-store" });
+      if (!response.ok) return;
+      const [latest] = await response.json();
+      if (!latest || latest.state !== "running") return;
+
+      state.jobId = latest.id;
+      showProcessingUI(true);
+      const progressBar = document.getElementById("progress-bar");
+      const progressMessage = document.getElementById("progress-message");
+      if (progressBar && latest.progress !== null) {
+        progressBar.style.width = `${latest.progress}%`;
+      }
+      if (progressMessage && latest.message) {
+        progressMessage.textContent = latest.message;
+      }
+    } catch (error) {
+      console.warn("Failed to check for a running install:", error);
     }
   };
 
@@ -99,7 +173,7 @@
         const urlInput = document.getElementById("url-input");
         const url = urlInput ? urlInput.value : "";
         if (url) {
-          await processRequest("/download", { url });
+          await processRequest("/api/v1/installs", { url });
         } else {
           showToast("Please enter a URL.", "red");
         }
@@ -267,6 +341,14 @@
         const data = JSON.parse(event.data);
         console.log("Event received:", data);
 
+        // Only events of the install being followed move the progress bar
+        if (
+          data.job_id === undefined ||
+          (state.jobId !== null && data.job_id !== state.jobId)
+        ) {
+          return;
+        }
+
         if (data.progress !== undefined && progressBar) {
           progressBar.style.width = `${data.progress}%`;
         }
@@ -341,6 +423,49 @@
     }, 60000); // 1 minute timeout
   };
 
+  /**
+   * Streams Foundry VTT output into the log panel while it is open.
+   */
+  const initLogPanel = () => {
+    const logPanel = document.getElementById("log-panel");
+    const logOutput = document.getElementById("log-output");
+    if (!logPanel || !logOutput) return;
+
+    const maxLines = 1000;
+    let logSource = null;
+
+    const appendLine = (entry) => {
+      const line = document.createElement("span");
+      line.className = `${entry.stream} ${entry.level}`;
+      line.textContent = `${entry.timestamp} ${entry.line}\n`;
+      logOutput.appendChild(line);
+      while (logOutput.childNodes.length > maxLines) {
+        logOutput.removeChild(logOutput.firstChild);
+      }
+      logOutput.scrollTop = logOutput.scrollHeight;
+    };
+
+    logPanel.addEventListener("toggle", () => {
+      if (logPanel.open && !logSource) {
+        logOutput.textContent = "";
+        logSource = new EventSource("/logs?backlog=200");
+        logSource.onmessage = (event) => {
+          try {
+            appendLine(JSON.parse(event.data));
+          } catch (error) {
+            console.error("Error processing log line:", error, event.data);
+          }
+        };
+        logSource.onerror = (error) => {
+          console.warn("Log stream connection error", error);
+        };
+      } else if (!logPanel.open && logSource) {
+        logSource.close();
+        logSource = null;
+      }
+    });
+  };
+
   /**
    * Attempts to redirect the user to the appropriate URL after process completion.
    */
@@ -465,10 +590,15 @@
   };
 
   // Initialize the application once the DOM is ready
-  document.addEventListener("DOMContentLoaded", () => {
+  document.addEventListener("DOMContentLoaded", async () => {
     initTabs();
     initUrlDownload();
     initFileUpload();
+    initLogPanel();
     switchTab("url"); // Set initial tab to URL tab
+    if (await ensureSignedIn()) {
+      showFailureBanner();
+      resumeRunningJob();
+    }
   });
 })();